@@ -1,38 +1,66 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    attr, coins, to_binary, Addr, BankMsg, Binary, Deps, DepsMut, Env, MessageInfo, Response,
-    StdError, StdResult, Uint128, Decimal
+    attr, from_binary, to_binary, Addr, Api, Binary, CosmosMsg, Decimal, Decimal256, Deps, DepsMut,
+    Empty, Env, Event, MessageInfo, Order, Response, StdError, StdResult, Storage, Uint128,
+    Uint256, Uint512,
 };
 
 use cw2::set_contract_version;
+use cw20::{Cw20QueryMsg, Cw20ReceiveMsg, Expiration, MarketingInfoResponse};
 use cw20_base::allowances::{
     deduct_allowance, execute_decrease_allowance, execute_increase_allowance, execute_send_from,
     execute_transfer_from, query_allowance,
 };
 use cw20_base::contract::{
-    execute_burn, execute_mint, execute_send, execute_transfer, query_balance, query_token_info,
+    execute_burn, execute_mint, execute_send, execute_transfer, execute_update_marketing,
+    execute_upload_logo, query_balance, query_download_logo, query_marketing_info, query_minter,
+    query_token_info,
+};
+use cw20_base::enumerable::{query_all_accounts, query_owner_allowances, query_spender_allowances};
+use cw20_base::state::{
+    MinterData, TokenInfo, ALLOWANCES, ALLOWANCES_SPENDER, MARKETING_INFO, TOKEN_INFO,
 };
-use cw20_base::state::{MinterData, TokenInfo, TOKEN_INFO};
 
-use crate::curves::DecimalPlaces;
+use crate::curves::{Curve, DecimalPlaces};
 use crate::error::ContractError;
-use crate::msg::{CurveFn, CurveInfoResponse, ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::state::{CurveState, Config, CURVE_STATE, CURVE_TYPE, CONFIG};
-use cw_utils::{must_pay, nonpayable};
+use crate::msg::{
+    BurnFromPayout, ConfigResponse, CurveFn, CurveInfoResponse, CurveType, ExecuteMsg,
+    FeeRecipient, FeeSplit, FundingPoolResponse, HatchAllowlistedResponse, InstantiateMsg,
+    MigrateMsg, PriceHistoryResponse, PriceObservation, QueryMsg, ReceiveMsg, ReconcileResponse,
+    ReserveAsset, SalePhase, SalePhaseResponse, SimulationResponse, TradeSide, TwapResponse,
+    UnrefundedReserve,
+};
+use crate::state::{
+    v0_1, Config, CurveState, PauseState, ADMIN, CONFIG, CURVE_STATE, CURVE_TYPE, HATCH_ALLOWLIST,
+    OBSERVATIONS, OBSERVATION_COUNT, PAUSED, PENDING_ADMIN, PRICE_HISTORY_SIZE,
+};
+use cw_utils::{ensure_from_older_version, must_pay, nonpayable};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:cw20-bonding";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// fees are expressed in basis points of the reserve amount
+const FEE_BPS_DENOMINATOR: u64 = 10_000;
+
+/// curve parameters and decimal places are scaled into 18 digit decimals
+const MAX_DECIMALS: u8 = 18;
+
+// settings for pagination
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     nonpayable(&info)?;
+    validate_token(&msg)?;
+    validate_curve(&msg.curve_type)?;
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
     // store token info using cw20-base format
@@ -43,27 +71,140 @@ pub fn instantiate(
         total_supply: Uint128::zero(),
         // set self as minter, so we can properly execute mint and burn
         mint: Some(MinterData {
-            minter: env.contract.address,
-            cap: None,
+            minter: env.contract.address.clone(),
+            cap: msg.supply_cap,
         }),
     };
     TOKEN_INFO.save(deps.storage, &data)?;
 
+    if let Some(marketing) = msg.marketing {
+        // let cw20-base check the logo and fields, acting as the marketing address until it's set
+        let self_info = MessageInfo {
+            sender: env.contract.address.clone(),
+            funds: vec![],
+        };
+        let data = MarketingInfoResponse {
+            marketing: Some(env.contract.address.clone()),
+            ..MarketingInfoResponse::default()
+        };
+        MARKETING_INFO.save(deps.storage, &data)?;
+        if let Some(logo) = marketing.logo {
+            execute_upload_logo(deps.branch(), env.clone(), self_info.clone(), logo)?;
+        }
+        execute_update_marketing(
+            deps.branch(),
+            env.clone(),
+            self_info,
+            marketing.project,
+            marketing.description,
+            Some(marketing.marketing.unwrap_or_default()),
+        )?;
+    }
+
     let places = DecimalPlaces::new(msg.decimals, msg.reserve_decimals);
-    let supply = CurveState::new(msg.reserve_denom, places);
+    if let ReserveAsset::Cw20 { address } = &msg.reserve_asset {
+        deps.api.addr_validate(address.as_str())?;
+    }
+    let mut supply = CurveState::new(msg.reserve_asset, places);
+    supply.supply_cap = msg.supply_cap;
+    if let Some(funding) = &msg.funding {
+        if funding.reserve_ratio_bps == 0
+            || funding.reserve_ratio_bps > FEE_BPS_DENOMINATOR
+            || funding.exit_tribute_bps > FEE_BPS_DENOMINATOR
+        {
+            return Err(ContractError::InvalidFunding {});
+        }
+        deps.api.addr_validate(funding.beneficiary.as_str())?;
+    }
+    supply.funding = msg.funding;
+    if let Some(hatch) = msg.hatch {
+        if hatch.price.is_zero() {
+            return Err(ContractError::InvalidHatchPrice {});
+        }
+        for address in hatch.allowlist {
+            let address = deps.api.addr_validate(&address)?;
+            HATCH_ALLOWLIST.save(deps.storage, &address, &Empty {})?;
+        }
+        supply.phase = SalePhase::Hatch {
+            price: hatch.price,
+            raise_target: hatch.raise_target,
+            raised: Uint128::zero(),
+        };
+    }
     CURVE_STATE.save(deps.storage, &supply)?;
 
     CURVE_TYPE.save(deps.storage, &msg.curve_type)?;
 
     let config = Config {
-        buy_fee: msg.buy_fee,
-        treasury: msg.treasury
+        buy_fee_bps: msg.buy_fee_bps,
+        sell_fee_bps: msg.sell_fee_bps,
+        treasury: msg.treasury,
+        fee_splits: msg.fee_splits,
+        unrefunded_reserve: msg.unrefunded_reserve,
+        burn_from_payout: msg.burn_from_payout,
     };
+    validate_config(deps.api, &config)?;
     CONFIG.save(deps.storage, &config)?;
 
+    let admin = match msg.admin {
+        Some(admin) => deps.api.addr_validate(&admin)?,
+        None => info.sender,
+    };
+    ADMIN.save(deps.storage, &admin)?;
+
     Ok(Response::default())
 }
 
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let original_version =
+        ensure_from_older_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    if original_version < "0.2.0".parse::<semver::Version>().unwrap() {
+        let admin = msg
+            .admin
+            .ok_or_else(|| ContractError::MigrateAdminRequired {
+                version: original_version.to_string(),
+            })?;
+        ADMIN.save(deps.storage, &deps.api.addr_validate(&admin)?)?;
+
+        // 0.1 only knew native reserves and a buy fee in whole percent
+        let state = v0_1::CURVE_STATE.load(deps.storage)?;
+        let reserve_asset = ReserveAsset::Native {
+            denom: state.reserve_denom,
+        };
+        let state = CurveState {
+            reserve: state.reserve,
+            supply: state.supply,
+            ..CurveState::new(reserve_asset, state.decimals)
+        };
+        CURVE_STATE.save(deps.storage, &state)?;
+        let config = v0_1::CONFIG.load(deps.storage)?;
+        let config = Config {
+            buy_fee_bps: config.buy_fee.saturating_mul(100),
+            sell_fee_bps: 0,
+            treasury: config.treasury,
+            fee_splits: vec![],
+            unrefunded_reserve: UnrefundedReserve::default(),
+            burn_from_payout: BurnFromPayout::default(),
+        };
+        validate_config(deps.api, &config)?;
+        CONFIG.save(deps.storage, &config)?;
+
+        // 0.1 used cw20-base 0.11, which didn't index allowances by spender
+        let data = ALLOWANCES
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for ((owner, spender), allowance) in data {
+            ALLOWANCES_SPENDER.save(deps.storage, (&spender, &owner), &allowance)?;
+        }
+    }
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("previous_version", original_version.to_string())
+        .add_attribute("new_version", CONTRACT_VERSION))
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
@@ -89,13 +230,110 @@ pub fn do_execute(
     curve_fn: CurveFn,
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::Buy {} => execute_buy(deps, env, info, curve_fn),
+        ExecuteMsg::Buy {
+            recipient,
+            min_tokens_out,
+            deadline,
+        } => {
+            let destination = BuyDestination::recipient(deps.api, recipient, &info.sender)?;
+            execute_buy(
+                deps,
+                env,
+                info,
+                curve_fn,
+                destination,
+                min_tokens_out,
+                deadline,
+            )
+        }
+        ExecuteMsg::BuyAndSend {
+            contract,
+            msg,
+            min_tokens_out,
+            deadline,
+        } => {
+            let destination = BuyDestination::contract(deps.api, contract, msg)?;
+            execute_buy(
+                deps,
+                env,
+                info,
+                curve_fn,
+                destination,
+                min_tokens_out,
+                deadline,
+            )
+        }
+        ExecuteMsg::Receive(msg) => execute_receive(deps, env, info, curve_fn, msg),
 
         // we override these from cw20
-        ExecuteMsg::Burn { amount, refund } => Ok(execute_sell(deps, env, info, curve_fn, amount, refund)?),
-        ExecuteMsg::BurnFrom { owner, amount, refund } => {
-            Ok(execute_sell_from(deps, env, info, curve_fn, owner, amount, refund)?)
+        ExecuteMsg::Burn {
+            amount,
+            refund,
+            recipient,
+            min_reserve_out,
+            deadline,
+        } => execute_sell(
+            deps,
+            env,
+            info,
+            curve_fn,
+            amount,
+            refund,
+            recipient,
+            min_reserve_out,
+            deadline,
+        ),
+        ExecuteMsg::BurnFrom {
+            owner,
+            amount,
+            refund,
+            recipient,
+            min_reserve_out,
+            deadline,
+        } => execute_sell_from(
+            deps,
+            env,
+            info,
+            curve_fn,
+            owner,
+            amount,
+            refund,
+            recipient,
+            min_reserve_out,
+            deadline,
+        ),
+
+        ExecuteMsg::UpdateConfig {
+            buy_fee_bps,
+            sell_fee_bps,
+            treasury,
+            fee_splits,
+            unrefunded_reserve,
+            burn_from_payout,
+        } => execute_update_config(
+            deps,
+            info,
+            buy_fee_bps,
+            sell_fee_bps,
+            treasury,
+            fee_splits,
+            unrefunded_reserve,
+            burn_from_payout,
+        ),
+        ExecuteMsg::UpdateCurve {
+            curve_type,
+            fit_reserve,
+        } => execute_update_curve(deps, env, info, curve_type, fit_reserve),
+        ExecuteMsg::TransferAdmin { new_admin } => execute_transfer_admin(deps, info, new_admin),
+        ExecuteMsg::AcceptAdmin {} => execute_accept_admin(deps, info),
+        ExecuteMsg::Pause { side } => execute_set_paused(deps, info, side, true),
+        ExecuteMsg::Unpause { side } => execute_set_paused(deps, info, side, false),
+        ExecuteMsg::UpdateHatchAllowlist { add, remove } => {
+            execute_update_hatch_allowlist(deps, info, add, remove)
         }
+        ExecuteMsg::OpenTrading {} => execute_open_trading(deps, info),
+        ExecuteMsg::WithdrawFunding { amount } => execute_withdraw_funding(deps, info, amount),
+        ExecuteMsg::Reconcile {} => execute_reconcile(deps, env, info),
 
         // these all come from cw20-base to implement the cw20 standard
         ExecuteMsg::Transfer { recipient, amount } => {
@@ -135,6 +373,42 @@ pub fn do_execute(
         } => Ok(execute_send_from(
             deps, env, info, owner, contract, amount, msg,
         )?),
+        ExecuteMsg::UpdateMarketing {
+            project,
+            description,
+            marketing,
+        } => Ok(execute_update_marketing(
+            deps,
+            env,
+            info,
+            project,
+            description,
+            marketing,
+        )?),
+        ExecuteMsg::UploadLogo(logo) => Ok(execute_upload_logo(deps, env, info, logo)?),
+    }
+}
+
+/// Where the tokens minted by a buy go
+pub enum BuyDestination {
+    /// credited to this account
+    Recipient(Addr),
+    /// credited to this contract, which is then called like a cw20 `Send` does
+    Contract { contract: Addr, msg: Binary },
+}
+
+impl BuyDestination {
+    fn recipient(api: &dyn Api, recipient: Option<String>, buyer: &Addr) -> StdResult<Self> {
+        let recipient = match recipient {
+            Some(recipient) => api.addr_validate(&recipient)?,
+            None => buyer.clone(),
+        };
+        Ok(BuyDestination::Recipient(recipient))
+    }
+
+    fn contract(api: &dyn Api, contract: String, msg: Binary) -> StdResult<Self> {
+        let contract = api.addr_validate(&contract)?;
+        Ok(BuyDestination::Contract { contract, msg })
     }
 }
 
@@ -143,65 +417,229 @@ pub fn execute_buy(
     env: Env,
     info: MessageInfo,
     curve_fn: CurveFn,
+    destination: BuyDestination,
+    min_tokens_out: Option<Uint128>,
+    deadline: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    let state = CURVE_STATE.load(deps.storage)?;
+    let payment = match &state.reserve_asset {
+        ReserveAsset::Native { denom } => must_pay(&info, denom)?,
+        ReserveAsset::Cw20 { .. } => return Err(ContractError::WrongReserveAsset {}),
+    };
+    do_buy(
+        deps,
+        env,
+        info.sender,
+        destination,
+        curve_fn,
+        payment,
+        min_tokens_out,
+        deadline,
+    )
+}
+
+/// Buys with cw20 reserve tokens, only the reserve token contract may call this
+pub fn execute_receive(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    curve_fn: CurveFn,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let state = CURVE_STATE.load(deps.storage)?;
+    match &state.reserve_asset {
+        ReserveAsset::Cw20 { address } if *address == info.sender => {}
+        _ => return Err(ContractError::WrongReserveAsset {}),
+    }
+
+    let buyer = deps.api.addr_validate(&wrapper.sender)?;
+    let (destination, min_tokens_out, deadline) = match from_binary(&wrapper.msg)? {
+        ReceiveMsg::Buy {
+            recipient,
+            min_tokens_out,
+            deadline,
+        } => (
+            BuyDestination::recipient(deps.api, recipient, &buyer)?,
+            min_tokens_out,
+            deadline,
+        ),
+        ReceiveMsg::BuyAndSend {
+            contract,
+            msg,
+            min_tokens_out,
+            deadline,
+        } => (
+            BuyDestination::contract(deps.api, contract, msg)?,
+            min_tokens_out,
+            deadline,
+        ),
+    };
+    do_buy(
+        deps,
+        env,
+        buyer,
+        destination,
+        curve_fn,
+        wrapper.amount,
+        min_tokens_out,
+        deadline,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn do_buy(
+    deps: DepsMut,
+    env: Env,
+    // buyer is the one who paid, refunds go back to them
+    buyer: Addr,
+    destination: BuyDestination,
+    curve_fn: CurveFn,
+    payment: Uint128,
+    min_tokens_out: Option<Uint128>,
+    deadline: Option<Expiration>,
 ) -> Result<Response, ContractError> {
+    check_deadline(&env, deadline)?;
+    if PAUSED.may_load(deps.storage)?.unwrap_or_default().buys {
+        return Err(ContractError::BuysPaused {});
+    }
     let mut state = CURVE_STATE.load(deps.storage)?;
     let config = CONFIG.load(deps.storage)?;
-
-    let mut payment = must_pay(&info, &state.reserve_denom)?;
-    let fee_amount = payment * Decimal::percent(config.buy_fee);
-    payment = payment - fee_amount;
+    if matches!(state.phase, SalePhase::Hatch { .. }) && !HATCH_ALLOWLIST.has(deps.storage, &buyer)
+    {
+        return Err(ContractError::NotAllowlisted {});
+    }
 
     // calculate how many tokens can be purchased with this and mint them
     let curve = curve_fn(state.decimals);
-    state.reserve += payment;
-    let new_supply = curve.supply(state.reserve);
-    let minted = new_supply
-        .checked_sub(state.supply)
-        .map_err(StdError::overflow)?;
-    state.supply = new_supply;
+    let spot_price_before = curve.spot_price(state.supply)?;
+    let trade = calc_buy(&state, &config, curve.as_ref(), payment)?;
+    let minted = trade.supply;
+    if minted.is_zero() && Some(state.supply) == state.supply_cap {
+        return Err(ContractError::SupplyCapReached {});
+    }
+    if let Some(min_tokens_out) = min_tokens_out {
+        if minted < min_tokens_out {
+            return Err(ContractError::MinTokensOut {
+                min_tokens_out,
+                tokens_out: minted,
+            });
+        }
+    }
+    let fee_amount = trade.fee;
+    // whatever the cap left unused goes back to the buyer
+    let refund = payment - trade.reserve;
+    let payment = trade.reserve - fee_amount;
+    let mut msgs = fee_messages(&config, &state.reserve_asset, fee_amount)?;
+    if !trade.surplus.is_zero() {
+        msgs.push(
+            state
+                .reserve_asset
+                .transfer_msg(&config.treasury, trade.surplus)?,
+        );
+    }
+    if !refund.is_zero() {
+        msgs.push(state.reserve_asset.transfer_msg(&buyer, refund)?);
+    }
+    state.reserve += payment - trade.donated - trade.surplus - trade.funding;
+    state.donated_reserve += trade.donated;
+    state.funding_pool += trade.funding;
+    state.supply += minted;
+    if let SalePhase::Hatch {
+        raise_target,
+        raised,
+        ..
+    } = &mut state.phase
+    {
+        *raised += payment;
+        if matches!(*raise_target, Some(target) if *raised >= target) {
+            state.phase = SalePhase::Open;
+        }
+    }
     CURVE_STATE.save(deps.storage, &state)?;
+    record_price(deps.storage, &env, curve.as_ref(), state.supply)?;
 
     // call into cw20-base to mint the token, call as self as no one else is allowed
     let sub_info = MessageInfo {
         sender: env.contract.address.clone(),
         funds: vec![],
     };
-    execute_mint(deps, env, sub_info, info.sender.to_string(), minted)?;
-
-    // now send the fee to the sender
-    let msg = BankMsg::Send {
-        to_address: config.treasury.to_string(),
-        amount: coins(fee_amount.u128(), state.reserve_denom),
+    let recipient = match destination {
+        BuyDestination::Recipient(recipient) => recipient,
+        BuyDestination::Contract { contract, msg } => {
+            // same as a cw20 send from the buyer
+            let hook = Cw20ReceiveMsg {
+                sender: buyer.to_string(),
+                amount: minted,
+                msg,
+            };
+            msgs.push(hook.into_cosmos_msg(&contract)?);
+            contract
+        }
     };
+    let event = trade_event(
+        TradeSide::Buy,
+        &buyer,
+        &recipient,
+        trade.reserve,
+        minted,
+        fee_amount,
+        spot_price_before,
+        &state,
+        curve.as_ref(),
+    )?;
+    execute_mint(deps, env, sub_info, recipient.to_string(), minted)?;
 
     // bond them to the validator
     let res = Response::new()
-        .add_message(msg)
+        .add_messages(msgs)
+        .add_event(event)
         .add_attribute("action", "buy")
-        .add_attribute("from", info.sender)
+        .add_attribute("from", buyer)
+        .add_attribute("to", recipient)
         .add_attribute("reserve", payment)
-        .add_attribute("supply", minted);
+        .add_attribute("supply", minted)
+        .add_attribute("fee", fee_amount)
+        .add_attribute("refund", refund);
     Ok(res)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn execute_sell(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     curve_fn: CurveFn,
     amount: Uint128,
-    refund: Option<bool>
+    refund: Option<bool>,
+    recipient: Option<String>,
+    min_reserve_out: Option<Uint128>,
+    deadline: Option<Expiration>,
 ) -> Result<Response, ContractError> {
     nonpayable(&info)?;
-    let receiver = info.sender.clone();
+    check_deadline(&env, deadline)?;
+    let receiver = match recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?,
+        None => info.sender.clone(),
+    };
     // do all the work
-    let mut res = do_sell(deps, env, info, curve_fn, receiver, amount, refund)?;
+    let mut res = do_sell(
+        deps,
+        env,
+        info,
+        curve_fn,
+        receiver,
+        amount,
+        refund,
+        min_reserve_out,
+    )?;
 
     // add our custom attributes
     res.attributes.push(attr("action", "burn"));
     Ok(res)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn execute_sell_from(
     deps: DepsMut,
     env: Env,
@@ -209,17 +647,28 @@ pub fn execute_sell_from(
     curve_fn: CurveFn,
     owner: String,
     amount: Uint128,
-    refund: Option<bool>
+    refund: Option<bool>,
+    recipient: Option<String>,
+    min_reserve_out: Option<Uint128>,
+    deadline: Option<Expiration>,
 ) -> Result<Response, ContractError> {
     nonpayable(&info)?;
+    check_deadline(&env, deadline)?;
     let owner_addr = deps.api.addr_validate(&owner)?;
     let spender_addr = info.sender.clone();
 
     // deduct allowance before doing anything else have enough allowance
     deduct_allowance(deps.storage, &owner_addr, &spender_addr, &env.block, amount)?;
 
+    // the config decides whether the spender or the owner is paid
+    let receiver_addr = match (CONFIG.load(deps.storage)?.burn_from_payout, recipient) {
+        (BurnFromPayout::Spender, Some(recipient)) => deps.api.addr_validate(&recipient)?,
+        (BurnFromPayout::Spender, None) => spender_addr.clone(),
+        (BurnFromPayout::Owner, Some(_)) => return Err(ContractError::BurnFromRecipient {}),
+        (BurnFromPayout::Owner, None) => owner_addr.clone(),
+    };
+
     // do all the work in do_sell
-    let receiver_addr = info.sender;
     let owner_info = MessageInfo {
         sender: owner_addr,
         funds: info.funds,
@@ -229,450 +678,3310 @@ pub fn execute_sell_from(
         env,
         owner_info,
         curve_fn,
-        receiver_addr.clone(),
+        receiver_addr,
         amount,
         refund,
+        min_reserve_out,
     )?;
 
     // add our custom attributes
     res.attributes.push(attr("action", "burn_from"));
-    res.attributes.push(attr("by", receiver_addr));
+    res.attributes.push(attr("by", spender_addr));
     Ok(res)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn do_sell(
     mut deps: DepsMut,
     env: Env,
     // info.sender is the one burning tokens
     info: MessageInfo,
     curve_fn: CurveFn,
-    // receiver is the one who gains (same for execute_sell, diff for execute_sell_from)
+    // receiver is the one who gets the refund (the sender or whoever execute_sell(_from) picked)
     receiver: Addr,
     amount: Uint128,
-    refund: Option<bool>
+    refund: Option<bool>,
+    min_reserve_out: Option<Uint128>,
 ) -> Result<Response, ContractError> {
+    if PAUSED.may_load(deps.storage)?.unwrap_or_default().sells {
+        return Err(ContractError::SellsPaused {});
+    }
+    if matches!(
+        CURVE_STATE.load(deps.storage)?.phase,
+        SalePhase::Hatch { .. }
+    ) {
+        return Err(ContractError::HatchInProgress {});
+    }
+
     // burn from the caller, this ensures there are tokens to cover this
-    execute_burn(deps.branch(), env, info.clone(), amount)?;
+    execute_burn(deps.branch(), env.clone(), info.clone(), amount)?;
 
-    // calculate how many tokens can be purchased with this and mint them
+    // calculate how many reserve tokens are released by this
     let mut state = CURVE_STATE.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
     let curve = curve_fn(state.decimals);
-    state.supply = state
-        .supply
-        .checked_sub(amount)
-        .map_err(StdError::overflow)?;
-    let new_reserve = curve.reserve(state.supply);
-    let released = state
-        .reserve
-        .checked_sub(new_reserve)
-        .map_err(StdError::overflow)?;
-    state.reserve = new_reserve;
-    CURVE_STATE.save(deps.storage, &state)?;
+    let spot_price_before = curve.spot_price(state.supply)?;
+    let trade = calc_sell(&state, &config, curve.as_ref(), amount)?;
+    let released = trade.reserve;
+    if let Some(min_reserve_out) = min_reserve_out {
+        if released < min_reserve_out {
+            return Err(ContractError::MinReserveOut {
+                min_reserve_out,
+                reserve_out: released,
+            });
+        }
+    }
+    state.supply -= amount;
+    state.reserve -= released + trade.fee + trade.funding - trade.donated;
+    state.donated_reserve -= trade.donated;
+    state.funding_pool += trade.funding;
 
-    // now send the tokens to the sender (TODO: for sell_from we do something else, right???)
-        
     let mut res = Response::new()
-        .add_attribute("from", info.sender)
+        .add_messages(fee_messages(&config, &state.reserve_asset, trade.fee)?)
+        .add_attribute("from", &info.sender)
         .add_attribute("supply", amount)
-        .add_attribute("reserve", released);
-    
-    if let Some(true) = refund {
-        let msg = BankMsg::Send {
-            to_address: receiver.to_string(),
-            amount: coins(released.u128(), state.reserve_denom),
-        };
-        res = res.add_message(msg);
+        .add_attribute("reserve", released)
+        .add_attribute("fee", trade.fee);
+
+    // where the released reserve ends up
+    // burning dust can release nothing, and the bank module rejects sending zero coins
+    let payout_to = if let Some(true) = refund {
+        if !released.is_zero() {
+            res = res.add_message(state.reserve_asset.transfer_msg(&receiver, released)?);
+        }
+        res = res.add_attribute("to", &receiver);
+        receiver
+    } else {
+        // nobody claimed the released reserve, account for it instead of leaving it stranded
+        res = res.add_attribute("unrefunded_to", config.unrefunded_reserve.as_str());
+        match config.unrefunded_reserve {
+            UnrefundedReserve::Donate => {
+                state.donated_reserve += released;
+                env.contract.address.clone()
+            }
+            UnrefundedReserve::Treasury => {
+                if !released.is_zero() {
+                    let msg = state
+                        .reserve_asset
+                        .transfer_msg(&config.treasury, released)?;
+                    res = res.add_message(msg);
+                }
+                config.treasury.clone()
+            }
+        }
+    };
+    // once the last tokens are burned nobody can claim the donated reserve, so the treasury gets it
+    if state.supply.is_zero() && !state.donated_reserve.is_zero() {
+        let msg = state
+            .reserve_asset
+            .transfer_msg(&config.treasury, state.donated_reserve)?;
+        res = res
+            .add_message(msg)
+            .add_attribute("donated_to_treasury", state.donated_reserve);
+        state.donated_reserve = Uint128::zero();
     }
+    CURVE_STATE.save(deps.storage, &state)?;
+    record_price(deps.storage, &env, curve.as_ref(), state.supply)?;
 
-    Ok(res)
+    let event = trade_event(
+        TradeSide::Sell,
+        &info.sender,
+        &payout_to,
+        released,
+        amount,
+        trade.fee,
+        spot_price_before,
+        &state,
+        curve.as_ref(),
+    )?;
+    Ok(res.add_event(event))
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    // default implementation stores curve info as enum, you can do something else in a derived
-    // contract and just pass in your custom curve to do_execute
-    let curve_type = CURVE_TYPE.load(deps.storage)?;
-    let curve_fn = curve_type.to_curve_fn();
-    do_query(deps, env, msg, curve_fn)
+/// Summary of a trade for indexers, emitted as a `wasm-bonding_trade` event. Amounts are named
+/// from the trader's side: `reserve_in` and `tokens_out` for buys, `tokens_in` and `reserve_out`
+/// for sells. The fee is in reserve tokens and part of `reserve_in`, or already taken from
+/// `reserve_out`
+#[allow(clippy::too_many_arguments)]
+fn trade_event(
+    side: TradeSide,
+    trader: &Addr,
+    recipient: &Addr,
+    reserve: Uint128,
+    tokens: Uint128,
+    fee: Uint128,
+    spot_price_before: Decimal,
+    state: &CurveState,
+    curve: &dyn Curve,
+) -> StdResult<Event> {
+    let (side, reserve_key, tokens_key) = match side {
+        TradeSide::Buy => ("buy", "reserve_in", "tokens_out"),
+        TradeSide::Sell => ("sell", "reserve_out", "tokens_in"),
+    };
+    Ok(Event::new("bonding_trade")
+        .add_attribute("side", side)
+        .add_attribute("trader", trader)
+        .add_attribute("recipient", recipient)
+        .add_attribute(reserve_key, reserve)
+        .add_attribute(tokens_key, tokens)
+        .add_attribute("fee", fee)
+        .add_attribute("spot_price_before", spot_price_before.to_string())
+        .add_attribute(
+            "spot_price_after",
+            curve.spot_price(state.supply)?.to_string(),
+        )
+        .add_attribute("supply_after", state.supply)
+        .add_attribute("reserve_after", state.reserve))
 }
 
-/// We pull out logic here, so we can import this from another contract and set a different Curve.
-/// This contacts sets a curve with an enum in InstantitateMsg and stored in state, but you may want
-/// to use custom math not included - make this easily reusable
-pub fn do_query(deps: Deps, _env: Env, msg: QueryMsg, curve_fn: CurveFn) -> StdResult<Binary> {
-    match msg {
-        // custom queries
-        QueryMsg::CurveInfo {} => to_binary(&query_curve_info(deps, curve_fn)?),
-        // inherited from cw20-base
-        QueryMsg::TokenInfo {} => to_binary(&query_token_info(deps)?),
-        QueryMsg::Balance { address } => to_binary(&query_balance(deps, address)?),
-        QueryMsg::Allowance { owner, spender } => {
-            to_binary(&query_allowance(deps, owner, spender)?)
+/// Stores the spot price after a trade. The previous price is accumulated for the time it held,
+/// so only the last trade in a block counts.
+fn record_price(
+    storage: &mut dyn Storage,
+    env: &Env,
+    curve: &dyn Curve,
+    supply: Uint128,
+) -> StdResult<()> {
+    let timestamp = env.block.time.seconds();
+    let count = OBSERVATION_COUNT.may_load(storage)?.unwrap_or_default();
+    let (index, cumulative_price) = match count.checked_sub(1) {
+        Some(last) => {
+            let last_observation = load_observation(storage, last)?;
+            if last_observation.timestamp == timestamp {
+                (last, last_observation.cumulative_price)
+            } else {
+                (count, cumulative_price_at(&last_observation, timestamp)?)
+            }
         }
-    }
+        None => (0, Decimal256::zero()),
+    };
+    let observation = PriceObservation {
+        timestamp,
+        spot_price: curve.spot_price(supply)?,
+        cumulative_price,
+    };
+    OBSERVATIONS.save(storage, index % PRICE_HISTORY_SIZE, &observation)?;
+    OBSERVATION_COUNT.save(storage, &(index + 1))
 }
 
-pub fn query_curve_info(deps: Deps, curve_fn: CurveFn) -> StdResult<CurveInfoResponse> {
-    let CurveState {
-        reserve,
-        supply,
-        reserve_denom,
-        decimals,
-    } = CURVE_STATE.load(deps.storage)?;
-
-    // This we can get from the local digits stored in instantiate
-    let curve = curve_fn(decimals);
-    let spot_price = curve.spot_price(supply);
-
-    Ok(CurveInfoResponse {
-        reserve,
-        supply,
-        spot_price,
-        reserve_denom,
-    })
+fn load_observation(storage: &dyn Storage, index: u64) -> StdResult<PriceObservation> {
+    OBSERVATIONS.load(storage, index % PRICE_HISTORY_SIZE)
 }
 
-// this is poor mans "skip" flag
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::msg::CurveType;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{coin, Decimal, OverflowError, OverflowOperation, StdError, SubMsg};
-    use cw_utils::PaymentError;
-
-    const DENOM: &str = "satoshi";
-    const CREATOR: &str = "creator";
-    const INVESTOR: &str = "investor";
-    const BUYER: &str = "buyer";
-    const TREASURY: &str = "treasury";
+/// The cumulative price at `timestamp`, assuming the observed price held since it was recorded
+fn cumulative_price_at(observation: &PriceObservation, timestamp: u64) -> StdResult<Decimal256> {
+    let elapsed = Decimal256::from_ratio(timestamp - observation.timestamp, 1u8);
+    let accrued = Decimal256::from(observation.spot_price).checked_mul(elapsed)?;
+    Ok(observation.cumulative_price.checked_add(accrued)?)
+}
 
-    fn default_instantiate(
-        decimals: u8,
-        reserve_decimals: u8,
-        curve_type: CurveType,
-    ) -> InstantiateMsg {
-        InstantiateMsg {
-            name: "Bonded".to_string(),
-            symbol: "EPOXY".to_string(),
-            decimals,
-            reserve_denom: DENOM.to_string(),
-            reserve_decimals,
-            curve_type,
-            buy_fee: 10u64,
-            treasury: Addr::unchecked(TREASURY)
+/// Index of the first stored observation recorded after `timestamp`, `count` if there is none
+fn first_observation_after(storage: &dyn Storage, count: u64, timestamp: u64) -> StdResult<u64> {
+    // observations are sorted by timestamp, so binary search the ones still in the buffer
+    let mut low = count.saturating_sub(PRICE_HISTORY_SIZE);
+    let mut high = count;
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if load_observation(storage, mid)?.timestamp <= timestamp {
+            low = mid + 1;
+        } else {
+            high = mid;
         }
     }
+    Ok(low)
+}
 
-    fn get_balance<U: Into<String>>(deps: Deps, addr: U) -> Uint128 {
-        query_balance(deps, addr.into()).unwrap().balance
+fn check_deadline(env: &Env, deadline: Option<Expiration>) -> Result<(), ContractError> {
+    match deadline {
+        Some(deadline) if deadline.is_expired(&env.block) => Err(ContractError::DeadlineExpired {}),
+        _ => Ok(()),
     }
+}
 
-    fn setup_test(deps: DepsMut, decimals: u8, reserve_decimals: u8, curve_type: CurveType) {
-        // this matches `linear_curve` test case from curves.rs
-        let creator = String::from(CREATOR);
-        let msg = default_instantiate(decimals, reserve_decimals, curve_type);
-        let info = mock_info(&creator, &[]);
+#[allow(clippy::too_many_arguments)]
+pub fn execute_update_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    buy_fee_bps: Option<u64>,
+    sell_fee_bps: Option<u64>,
+    treasury: Option<Addr>,
+    fee_splits: Option<Vec<FeeSplit>>,
+    unrefunded_reserve: Option<UnrefundedReserve>,
+    burn_from_payout: Option<BurnFromPayout>,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    assert_admin(deps.as_ref(), &info.sender)?;
 
-        // make sure we can instantiate with this
-        let res = instantiate(deps, mock_env(), info, msg).unwrap();
-        assert_eq!(0, res.messages.len());
+    let mut config = CONFIG.load(deps.storage)?;
+    if let Some(buy_fee_bps) = buy_fee_bps {
+        config.buy_fee_bps = buy_fee_bps;
+    }
+    if let Some(sell_fee_bps) = sell_fee_bps {
+        config.sell_fee_bps = sell_fee_bps;
+    }
+    if let Some(treasury) = treasury {
+        config.treasury = treasury;
+    }
+    if let Some(fee_splits) = fee_splits {
+        config.fee_splits = fee_splits;
     }
+    if let Some(unrefunded_reserve) = unrefunded_reserve {
+        config.unrefunded_reserve = unrefunded_reserve;
+    }
+    if let Some(burn_from_payout) = burn_from_payout {
+        config.burn_from_payout = burn_from_payout;
+    }
+    validate_config(deps.api, &config)?;
+    CONFIG.save(deps.storage, &config)?;
 
-    #[test]
-    fn proper_instantiation() {
-        let mut deps = mock_dependencies();
+    Ok(Response::new().add_attribute("action", "update_config"))
+}
 
-        // this matches `linear_curve` test case from curves.rs
-        let creator = String::from("creator");
-        let curve_type = CurveType::SquareRoot {
-            slope: Uint128::new(1),
-            scale: 1,
-        };
-        let msg = default_instantiate(2, 8, curve_type.clone());
-        let info = mock_info(&creator, &[]);
+pub fn execute_update_curve(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    curve_type: CurveType,
+    fit_reserve: bool,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    assert_admin(deps.as_ref(), &info.sender)?;
+    validate_curve(&curve_type)?;
 
-        // make sure we can instantiate with this
-        let res = instantiate(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
-        assert_eq!(0, res.messages.len());
+    let mut state = CURVE_STATE.load(deps.storage)?;
+    let reserve_for = |curve_type: &CurveType| {
+        let curve = curve_type.to_curve_fn()(state.decimals);
+        curve.reserve(state.supply)
+    };
+    let mut curve_type = curve_type;
+    if fit_reserve && !state.supply.is_zero() {
+        let required = reserve_for(&curve_type)?;
+        if required.is_zero() {
+            return Err(ContractError::InvalidCurve {
+                reason: "the curve prices the supply at zero".to_string(),
+            });
+        }
+        curve_type = scale_curve(curve_type, state.reserve, required)?;
+        validate_curve(&curve_type)?;
+    }
 
-        // token info is proper
-        let token = query_token_info(deps.as_ref()).unwrap();
-        assert_eq!(&token.name, &msg.name);
-        assert_eq!(&token.symbol, &msg.symbol);
-        assert_eq!(token.decimals, 2);
-        assert_eq!(token.total_supply, Uint128::zero());
+    // rounding may leave the fitted curve a bit under the reserve, never over it
+    let required = reserve_for(&curve_type)?;
+    if required > state.reserve {
+        return Err(ContractError::InsufficientReserve {
+            required,
+            available: state.reserve,
+        });
+    }
+    // the new curve only backs what it needs, the rest is paid out pro rata on sells
+    let surplus = state.reserve - required;
+    state.reserve = required;
+    state.donated_reserve += surplus;
+    CURVE_STATE.save(deps.storage, &state)?;
+    CURVE_TYPE.save(deps.storage, &curve_type)?;
 
-        // curve state is sensible
-        let state = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
-        assert_eq!(state.reserve, Uint128::zero());
-        assert_eq!(state.supply, Uint128::zero());
-        assert_eq!(state.reserve_denom.as_str(), DENOM);
-        // spot price 0 as supply is 0
-        assert_eq!(state.spot_price, Decimal::zero());
+    let curve = curve_type.to_curve_fn()(state.decimals);
+    record_price(deps.storage, &env, curve.as_ref(), state.supply)?;
 
-        // curve type is stored properly
-        let curve = CURVE_TYPE.load(&deps.storage).unwrap();
-        assert_eq!(curve_type, curve);
+    Ok(Response::new()
+        .add_attribute("action", "update_curve")
+        .add_attribute("reserve", required)
+        .add_attribute("donated", surplus))
+}
+
+/// Multiplies the slope (or value) of the curve by `num / den`, which scales its reserve the same
+fn scale_curve(
+    curve_type: CurveType,
+    num: Uint128,
+    den: Uint128,
+) -> Result<CurveType, ContractError> {
+    // the result gets as many decimal places as fit, so rounding it down costs next to nothing.
+    // Trailing zeros are dropped again, but never below the scale it had
+    let fit = |x: Uint128, scale: u32| -> StdResult<(Uint128, u32)> {
+        let ten = Uint512::from(10u32);
+        let mut extra = MAX_DECIMALS as u32 - scale;
+        let mut value = Uint512::from(x) * Uint512::from(num) * ten.pow(extra) / Uint512::from(den);
+        while extra > 0 && (value > Uint512::from(Uint128::MAX) || (value % ten).is_zero()) {
+            value /= ten;
+            extra -= 1;
+        }
+        Ok((value.try_into()?, scale + extra))
+    };
+    let curve_type = match curve_type {
+        CurveType::Constant { value, scale } => {
+            let (value, scale) = fit(value, scale)?;
+            CurveType::Constant { value, scale }
+        }
+        CurveType::Linear { slope, scale } => {
+            let (slope, scale) = fit(slope, scale)?;
+            CurveType::Linear { slope, scale }
+        }
+        CurveType::SquareRoot { slope, scale } => {
+            let (slope, scale) = fit(slope, scale)?;
+            CurveType::SquareRoot { slope, scale }
+        }
+        // it only prices whole reserve units of slope, a fitted fraction would be donated
+        CurveType::Exponential { .. } => {
+            return Err(ContractError::InvalidCurve {
+                reason: "exponential curves can't be fitted to the reserve".to_string(),
+            })
+        }
+        CurveType::Power {
+            slope,
+            scale,
+            exponent_num,
+            exponent_den,
+        } => {
+            let (slope, scale) = fit(slope, scale)?;
+            CurveType::Power {
+                slope,
+                scale,
+                exponent_num,
+                exponent_den,
+            }
+        }
+        CurveType::Sigmoid {
+            max_price,
+            scale,
+            inflection,
+            steepness,
+        } => {
+            let (max_price, scale) = fit(max_price, scale)?;
+            CurveType::Sigmoid {
+                max_price,
+                scale,
+                inflection,
+                steepness,
+            }
+        }
+        CurveType::Piecewise { .. } => {
+            return Err(ContractError::InvalidCurve {
+                reason: "piecewise curves can't be fitted to the reserve".to_string(),
+            })
+        }
+    };
+    Ok(curve_type)
+}
+
+pub fn execute_transfer_admin(
+    deps: DepsMut,
+    info: MessageInfo,
+    new_admin: String,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    assert_admin(deps.as_ref(), &info.sender)?;
+
+    let new_admin = deps.api.addr_validate(&new_admin)?;
+    PENDING_ADMIN.save(deps.storage, &new_admin)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "transfer_admin")
+        .add_attribute("pending_admin", new_admin))
+}
+
+pub fn execute_accept_admin(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let pending = PENDING_ADMIN
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoPendingAdmin {})?;
+    if pending != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    ADMIN.save(deps.storage, &pending)?;
+    PENDING_ADMIN.remove(deps.storage);
+
+    Ok(Response::new()
+        .add_attribute("action", "accept_admin")
+        .add_attribute("admin", pending))
+}
+
+/// Pauses or unpauses one side of the market, or both if `side` is None
+pub fn execute_set_paused(
+    deps: DepsMut,
+    info: MessageInfo,
+    side: Option<TradeSide>,
+    paused: bool,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    assert_admin(deps.as_ref(), &info.sender)?;
+
+    let mut state = PAUSED.may_load(deps.storage)?.unwrap_or_default();
+    match side {
+        Some(TradeSide::Buy) => state.buys = paused,
+        Some(TradeSide::Sell) => state.sells = paused,
+        None => {
+            state.buys = paused;
+            state.sells = paused;
+        }
+    }
+    PAUSED.save(deps.storage, &state)?;
+
+    let action = if paused { "pause" } else { "unpause" };
+    Ok(Response::new()
+        .add_attribute("action", action)
+        .add_attribute("buys_paused", state.buys.to_string())
+        .add_attribute("sells_paused", state.sells.to_string()))
+}
+
+pub fn execute_update_hatch_allowlist(
+    deps: DepsMut,
+    info: MessageInfo,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    assert_admin(deps.as_ref(), &info.sender)?;
+
+    for address in add {
+        let address = deps.api.addr_validate(&address)?;
+        HATCH_ALLOWLIST.save(deps.storage, &address, &Empty {})?;
+    }
+    for address in remove {
+        let address = deps.api.addr_validate(&address)?;
+        HATCH_ALLOWLIST.remove(deps.storage, &address);
+    }
+
+    Ok(Response::new().add_attribute("action", "update_hatch_allowlist"))
+}
+
+pub fn execute_open_trading(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    assert_admin(deps.as_ref(), &info.sender)?;
+
+    let mut state = CURVE_STATE.load(deps.storage)?;
+    if state.phase == SalePhase::Open {
+        return Err(ContractError::HatchOver {});
+    }
+    state.phase = SalePhase::Open;
+    CURVE_STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new().add_attribute("action", "open_trading"))
+}
+
+pub fn execute_withdraw_funding(
+    deps: DepsMut,
+    info: MessageInfo,
+    amount: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    let mut state = CURVE_STATE.load(deps.storage)?;
+    match &state.funding {
+        Some(funding) if funding.beneficiary == info.sender => {}
+        _ => return Err(ContractError::Unauthorized {}),
+    }
+
+    let amount = amount.unwrap_or(state.funding_pool);
+    state.funding_pool =
+        state
+            .funding_pool
+            .checked_sub(amount)
+            .map_err(|_| ContractError::InsufficientFunding {
+                available: state.funding_pool,
+            })?;
+    CURVE_STATE.save(deps.storage, &state)?;
+
+    let mut res = Response::new()
+        .add_attribute("action", "withdraw_funding")
+        .add_attribute("amount", amount);
+    if !amount.is_zero() {
+        res = res.add_message(state.reserve_asset.transfer_msg(&info.sender, amount)?);
+    }
+    Ok(res)
+}
+
+/// Reports how far the reserve held by the contract drifted from what the curve state tracks.
+/// It does not change any state, the admin decides what to do with a surplus
+pub fn execute_reconcile(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+    assert_admin(deps.as_ref(), &info.sender)?;
+
+    let state = CURVE_STATE.load(deps.storage)?;
+    let balance = match &state.reserve_asset {
+        ReserveAsset::Native { denom } => {
+            deps.querier
+                .query_balance(&env.contract.address, denom)?
+                .amount
+        }
+        ReserveAsset::Cw20 { address } => {
+            let res: cw20::BalanceResponse = deps.querier.query_wasm_smart(
+                address,
+                &Cw20QueryMsg::Balance {
+                    address: env.contract.address.to_string(),
+                },
+            )?;
+            res.balance
+        }
+    };
+    let tracked = state.reserve + state.donated_reserve + state.funding_pool;
+    let reconciled = ReconcileResponse {
+        balance,
+        tracked,
+        surplus: balance.saturating_sub(tracked),
+        shortfall: tracked.saturating_sub(balance),
+    };
+
+    Ok(Response::new()
+        .add_attribute("action", "reconcile")
+        .add_attribute("balance", balance)
+        .add_attribute("tracked", tracked)
+        .add_attribute("surplus", reconciled.surplus)
+        .add_attribute("shortfall", reconciled.shortfall)
+        .set_data(to_binary(&reconciled)?))
+}
+
+fn assert_admin(deps: Deps, sender: &Addr) -> Result<(), ContractError> {
+    match ADMIN.may_load(deps.storage)? {
+        Some(admin) if admin == *sender => Ok(()),
+        _ => Err(ContractError::Unauthorized {}),
+    }
+}
+
+/// Highest exponent numerator or denominator a Power curve may use, higher ones leave
+/// too little precision in the root helpers
+const MAX_POWER_EXPONENT: u32 = 10;
+
+/// Checks the token metadata the same way cw20-base does, plus what the curve math relies on
+fn validate_token(msg: &InstantiateMsg) -> Result<(), ContractError> {
+    if !(3..=50).contains(&msg.name.len()) {
+        return Err(ContractError::InvalidName {});
+    }
+    if !(3..=12).contains(&msg.symbol.len())
+        || !msg
+            .symbol
+            .bytes()
+            .all(|b| b.is_ascii_alphabetic() || b == b'-')
+    {
+        return Err(ContractError::InvalidSymbol {});
+    }
+    if msg.decimals > MAX_DECIMALS {
+        return Err(ContractError::InvalidDecimals {
+            decimals: msg.decimals,
+        });
+    }
+    if msg.reserve_decimals > MAX_DECIMALS {
+        return Err(ContractError::InvalidReserveDecimals {
+            decimals: msg.reserve_decimals,
+        });
+    }
+    if matches!(&msg.reserve_asset, ReserveAsset::Native { denom } if denom.is_empty()) {
+        return Err(ContractError::InvalidReserveDenom {});
+    }
+    if msg.supply_cap == Some(Uint128::zero()) {
+        return Err(ContractError::InvalidSupplyCap {});
+    }
+    Ok(())
+}
+
+fn validate_curve(curve_type: &CurveType) -> Result<(), ContractError> {
+    let invalid = |reason: &str| {
+        Err(ContractError::InvalidCurve {
+            reason: reason.to_string(),
+        })
+    };
+    let scales = match curve_type {
+        CurveType::Constant { scale, .. }
+        | CurveType::Linear { scale, .. }
+        | CurveType::SquareRoot { scale, .. }
+        | CurveType::Exponential { scale, .. }
+        | CurveType::Power { scale, .. }
+        | CurveType::Sigmoid { scale, .. } => vec![*scale],
+        CurveType::Piecewise { segments } => segments.iter().map(|s| s.scale).collect(),
+    };
+    if let Some(&scale) = scales.iter().find(|&&scale| scale > MAX_DECIMALS as u32) {
+        return Err(ContractError::InvalidCurveScale { scale });
+    }
+
+    match curve_type {
+        CurveType::Constant { value: slope, .. }
+        | CurveType::Linear { slope, .. }
+        | CurveType::SquareRoot { slope, .. }
+        | CurveType::Exponential { slope, .. }
+            if slope.is_zero() =>
+        {
+            return Err(ContractError::InvalidCurveSlope {});
+        }
+        CurveType::Power {
+            slope,
+            exponent_num,
+            exponent_den,
+            ..
+        } => {
+            if slope.is_zero() {
+                return Err(ContractError::InvalidCurveSlope {});
+            }
+            if *exponent_num > MAX_POWER_EXPONENT
+                || *exponent_den == 0
+                || *exponent_den > MAX_POWER_EXPONENT
+            {
+                return invalid(&format!(
+                    "exponent_num must be at most {0}, exponent_den between 1 and {0}",
+                    MAX_POWER_EXPONENT
+                ));
+            }
+        }
+        CurveType::Piecewise { segments } => {
+            match segments.first() {
+                Some(first) if first.start.is_zero() => {}
+                _ => return invalid("first segment must start at 0"),
+            }
+            if segments.windows(2).any(|w| w[0].start >= w[1].start) {
+                return invalid("segments must be sorted by start");
+            }
+            if segments
+                .iter()
+                .any(|s| s.price.is_zero() && s.slope.is_zero())
+            {
+                return invalid("segments need a price or a slope");
+            }
+        }
+        CurveType::Sigmoid {
+            max_price,
+            steepness,
+            ..
+        } if max_price.is_zero() || steepness.is_zero() => {
+            return invalid("max_price and steepness must be more than zero");
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn validate_config(api: &dyn Api, config: &Config) -> Result<(), ContractError> {
+    for fee_bps in [config.buy_fee_bps, config.sell_fee_bps] {
+        if fee_bps > FEE_BPS_DENOMINATOR {
+            return Err(ContractError::InvalidFee { fee_bps });
+        }
+    }
+    api.addr_validate(config.treasury.as_str())?;
+
+    let mut total_weight = 0u64;
+    for split in &config.fee_splits {
+        if let FeeRecipient::Address { address } = &split.recipient {
+            api.addr_validate(address.as_str())?;
+        }
+        total_weight = total_weight
+            .checked_add(split.weight)
+            .ok_or(ContractError::InvalidFeeSplits {})?;
+    }
+    if !config.fee_splits.is_empty() && total_weight == 0 {
+        return Err(ContractError::InvalidFeeSplits {});
+    }
+    Ok(())
+}
+
+/// Splits `fee` between the configured recipients by weight, rounding dust goes to the treasury.
+/// Zero amounts are skipped, as the bank module rejects sending no coins
+fn fee_messages(config: &Config, asset: &ReserveAsset, fee: Uint128) -> StdResult<Vec<CosmosMsg>> {
+    let mut msgs: Vec<CosmosMsg> = vec![];
+    if fee.is_zero() {
+        return Ok(msgs);
+    }
+
+    let total_weight: u64 = config.fee_splits.iter().map(|split| split.weight).sum();
+    let mut to_treasury = fee;
+    for split in &config.fee_splits {
+        let share = fee.multiply_ratio(split.weight, total_weight);
+        if share.is_zero() {
+            continue;
+        }
+        match &split.recipient {
+            // whatever is left over is sent below in one message
+            FeeRecipient::Treasury => continue,
+            FeeRecipient::Address { address } => msgs.push(asset.transfer_msg(address, share)?),
+            FeeRecipient::Burn => msgs.push(asset.burn_msg(share)?),
+        }
+        to_treasury -= share;
+    }
+
+    if !to_treasury.is_zero() {
+        msgs.push(asset.transfer_msg(&config.treasury, to_treasury)?);
+    }
+    Ok(msgs)
+}
+
+/// Reserve and supply amounts moved by a single buy or sell. We use the same math when executing
+/// and when simulating trades, so the queries always match what the contract will do
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TradeAmounts {
+    /// reserve paid in (buy, fee included) or released to the seller (sell, fee deducted)
+    pub reserve: Uint128,
+    /// reserve that goes to the fee recipients
+    pub fee: Uint128,
+    /// supply minted (buy) or burned (sell)
+    pub supply: Uint128,
+    /// part of reserve paid into (buy) or taken from (sell) the donated reserve
+    pub donated: Uint128,
+    /// part of reserve paid above the curve during the hatch, it goes to the treasury
+    pub surplus: Uint128,
+    /// part of reserve that goes to the funding pool, on buys and as exit tribute on sells
+    pub funding: Uint128,
+}
+
+/// Splits the fee off `payment` and calculates how many tokens the rest buys.
+/// With a supply cap, only the part of `payment` needed to reach it is used
+pub fn calc_buy(
+    state: &CurveState,
+    config: &Config,
+    curve: &dyn Curve,
+    payment: Uint128,
+) -> StdResult<TradeAmounts> {
+    let payment = match state.supply_cap {
+        Some(cap) => payment.min(gross_up(buy_cost(state, curve, cap)?, config.buy_fee_bps)?),
+        None => payment,
+    };
+
+    let fee = payment.multiply_ratio(config.buy_fee_bps, FEE_BPS_DENOMINATOR);
+    let net = payment.checked_sub(fee).map_err(StdError::overflow)?;
+    // only the reserve ratio of the payment backs the curve
+    let funding = net.multiply_ratio(funding_bps(state), FEE_BPS_DENOMINATOR);
+    let room = state
+        .supply_cap
+        .map_or(Uint128::MAX, |cap| cap.saturating_sub(state.supply));
+
+    match state.phase {
+        SalePhase::Hatch { price, .. } => {
+            let minted = hatch_supply(state.decimals, price, net)?.min(room);
+            // the curve keeps the reserve it needs for the new supply, above it is surplus
+            let new_supply = state
+                .supply
+                .checked_add(minted)
+                .map_err(StdError::overflow)?;
+            let needed = curve.reserve(new_supply)?.saturating_sub(state.reserve);
+            let surplus = (net - funding)
+                .checked_sub(needed)
+                .map_err(|_| StdError::generic_err("hatch price is below the curve"))?;
+            Ok(TradeAmounts {
+                reserve: payment,
+                fee,
+                supply: minted,
+                donated: Uint128::zero(),
+                surplus,
+                funding,
+            })
+        }
+        SalePhase::Open => {
+            // buyers pay into the donated reserve in proportion, so they don't dilute it, unless
+            // nothing is bonded: all of `net` would go there and mint nothing
+            let net = net - funding;
+            let donated = if state.reserve.is_zero() {
+                Uint128::zero()
+            } else {
+                net.multiply_ratio(state.donated_reserve, state.reserve + state.donated_reserve)
+            };
+            let new_reserve = state
+                .reserve
+                .checked_add(net - donated)
+                .map_err(StdError::overflow)?;
+            let new_supply = curve.supply(new_reserve)?;
+            let minted = new_supply
+                .checked_sub(state.supply)
+                .map_err(StdError::overflow)?;
+            Ok(TradeAmounts {
+                reserve: payment,
+                fee,
+                supply: minted.min(room),
+                donated,
+                surplus: Uint128::zero(),
+                funding,
+            })
+        }
+    }
+}
+
+/// Reserve needed to buy up to `new_supply`, before the buy fee
+fn buy_cost(state: &CurveState, curve: &dyn Curve, new_supply: Uint128) -> StdResult<Uint128> {
+    let amount = new_supply.saturating_sub(state.supply);
+    match state.phase {
+        SalePhase::Hatch { price, .. } => hatch_cost(state.decimals, price, amount),
+        SalePhase::Open => {
+            let mut net = curve.reserve(new_supply)?.saturating_sub(state.reserve);
+            if !state.reserve.is_zero() {
+                // calc_buy moves part of the payment into the donated reserve
+                net = net.multiply_ratio(state.reserve + state.donated_reserve, state.reserve);
+            }
+            // and part of it into the funding pool
+            gross_up(net, funding_bps(state))
+        }
+    }
+}
+
+/// Part of each buy, in basis points, that goes to the funding pool instead of the reserve
+fn funding_bps(state: &CurveState) -> u64 {
+    state
+        .funding
+        .as_ref()
+        .map_or(0, |funding| FEE_BPS_DENOMINATOR - funding.reserve_ratio_bps)
+}
+
+/// Part of the released reserve, in basis points, that sellers leave in the funding pool
+fn exit_tribute_bps(state: &CurveState) -> u64 {
+    state
+        .funding
+        .as_ref()
+        .map_or(0, |funding| funding.exit_tribute_bps)
+}
+
+/// Supply tokens that `net` reserve buys at the fixed hatch price
+fn hatch_supply(decimals: DecimalPlaces, price: Decimal, net: Uint128) -> StdResult<Uint128> {
+    // price is in whole tokens like the spot price, so scale by the decimal places
+    let numerator =
+        net.full_mul(10u128.pow(decimals.supply)) * Uint256::from(Decimal::one().atomics());
+    let denominator = Uint256::from(10u128.pow(decimals.reserve)) * Uint256::from(price.atomics());
+    Ok((numerator / denominator).try_into()?)
+}
+
+/// Reserve needed to buy `amount` supply tokens at the fixed hatch price, rounded up
+fn hatch_cost(decimals: DecimalPlaces, price: Decimal, amount: Uint128) -> StdResult<Uint128> {
+    let numerator = amount.full_mul(10u128.pow(decimals.reserve)) * Uint256::from(price.atomics());
+    let denominator =
+        Uint256::from(10u128.pow(decimals.supply)) * Uint256::from(Decimal::one().atomics());
+    let cost = (numerator + denominator - Uint256::one()) / denominator;
+    Ok(cost.try_into()?)
+}
+
+/// Calculates how much reserve is released by burning `amount` tokens, including their share
+/// of the donated reserve, and splits the fee off it
+pub fn calc_sell(
+    state: &CurveState,
+    config: &Config,
+    curve: &dyn Curve,
+    amount: Uint128,
+) -> StdResult<TradeAmounts> {
+    let new_supply = state
+        .supply
+        .checked_sub(amount)
+        .map_err(StdError::overflow)?;
+    // reserve bonded by 0.1, whose roots were cut at a few decimal places, can sit a few units
+    // below what the curve asks for now. Small sells release nothing until that is made up
+    let released = state.reserve.saturating_sub(curve.reserve(new_supply)?);
+    let donated = if amount.is_zero() {
+        Uint128::zero()
+    } else {
+        state.donated_reserve.multiply_ratio(amount, state.supply)
+    };
+    let released = released + donated;
+    let fee = released.multiply_ratio(config.sell_fee_bps, FEE_BPS_DENOMINATOR);
+    let funding = released.multiply_ratio(exit_tribute_bps(state), FEE_BPS_DENOMINATOR);
+    let reserve = released
+        .checked_sub(fee + funding)
+        .map_err(StdError::overflow)?;
+    Ok(TradeAmounts {
+        reserve,
+        fee,
+        supply: amount,
+        donated,
+        surplus: Uint128::zero(),
+        funding,
+    })
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    // default implementation stores curve info as enum, you can do something else in a derived
+    // contract and just pass in your custom curve to do_execute
+    let curve_type = CURVE_TYPE.load(deps.storage)?;
+    let curve_fn = curve_type.to_curve_fn();
+    do_query(deps, env, msg, curve_fn)
+}
+
+/// We pull out logic here, so we can import this from another contract and set a different Curve.
+/// This contacts sets a curve with an enum in InstantitateMsg and stored in state, but you may want
+/// to use custom math not included - make this easily reusable
+pub fn do_query(deps: Deps, env: Env, msg: QueryMsg, curve_fn: CurveFn) -> StdResult<Binary> {
+    match msg {
+        // custom queries
+        QueryMsg::CurveInfo {} => to_binary(&query_curve_info(deps, curve_fn)?),
+        QueryMsg::SimulateBuy { reserve_amount } => {
+            to_binary(&query_simulate_buy(deps, curve_fn, reserve_amount)?)
+        }
+        QueryMsg::SimulateSell { supply_amount } => {
+            to_binary(&query_simulate_sell(deps, curve_fn, supply_amount)?)
+        }
+        QueryMsg::ReverseSimulateBuy { supply_amount } => {
+            to_binary(&query_reverse_simulate_buy(deps, curve_fn, supply_amount)?)
+        }
+        QueryMsg::ReverseSimulateSell { reserve_amount } => to_binary(
+            &query_reverse_simulate_sell(deps, curve_fn, reserve_amount)?,
+        ),
+        QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::SalePhase {} => to_binary(&query_sale_phase(deps)?),
+        QueryMsg::HatchAllowlisted { address } => {
+            to_binary(&query_hatch_allowlisted(deps, address)?)
+        }
+        QueryMsg::FundingPool {} => to_binary(&query_funding_pool(deps)?),
+        QueryMsg::Twap { window_seconds } => to_binary(&query_twap(deps, env, window_seconds)?),
+        QueryMsg::PriceHistory { start_after, limit } => {
+            to_binary(&query_price_history(deps, start_after, limit)?)
+        }
+        // inherited from cw20-base
+        QueryMsg::TokenInfo {} => to_binary(&query_token_info(deps)?),
+        QueryMsg::Balance { address } => to_binary(&query_balance(deps, address)?),
+        QueryMsg::Allowance { owner, spender } => {
+            to_binary(&query_allowance(deps, owner, spender)?)
+        }
+        QueryMsg::Minter {} => to_binary(&query_minter(deps)?),
+        QueryMsg::AllAllowances {
+            owner,
+            start_after,
+            limit,
+        } => to_binary(&query_owner_allowances(deps, owner, start_after, limit)?),
+        QueryMsg::AllSpenderAllowances {
+            spender,
+            start_after,
+            limit,
+        } => to_binary(&query_spender_allowances(
+            deps,
+            spender,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::AllAccounts { start_after, limit } => {
+            to_binary(&query_all_accounts(deps, start_after, limit)?)
+        }
+        QueryMsg::MarketingInfo {} => to_binary(&query_marketing_info(deps)?),
+        QueryMsg::DownloadLogo {} => to_binary(&query_download_logo(deps)?),
+    }
+}
+
+pub fn query_curve_info(deps: Deps, curve_fn: CurveFn) -> StdResult<CurveInfoResponse> {
+    let CurveState {
+        reserve,
+        supply,
+        donated_reserve,
+        reserve_asset,
+        decimals,
+        ..
+    } = CURVE_STATE.load(deps.storage)?;
+
+    // This we can get from the local digits stored in instantiate
+    let curve = curve_fn(decimals);
+    let spot_price = curve.spot_price(supply)?;
+
+    Ok(CurveInfoResponse {
+        reserve,
+        supply,
+        donated_reserve,
+        spot_price,
+        reserve_asset,
+    })
+}
+
+pub fn query_twap(deps: Deps, env: Env, window_seconds: u64) -> StdResult<TwapResponse> {
+    if window_seconds == 0 {
+        return Err(StdError::generic_err(
+            "TWAP window must be at least one second",
+        ));
+    }
+    let not_enough_history = || StdError::generic_err("Not enough price history for TWAP window");
+    let now = env.block.time.seconds();
+    let start = now
+        .checked_sub(window_seconds)
+        .ok_or_else(not_enough_history)?;
+
+    let count = OBSERVATION_COUNT
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    // the observation in effect at the start of the window must still be in the buffer
+    let first = first_observation_after(deps.storage, count, start)?;
+    if first == count.saturating_sub(PRICE_HISTORY_SIZE) {
+        return Err(not_enough_history());
+    }
+    let start_observation = load_observation(deps.storage, first - 1)?;
+    let last_observation = load_observation(deps.storage, count - 1)?;
+
+    let accrued = cumulative_price_at(&last_observation, now)?
+        - cumulative_price_at(&start_observation, start)?;
+    let twap = accrued / Decimal256::from_ratio(window_seconds, 1u8);
+    Ok(TwapResponse {
+        twap: Decimal::new(twap.atomics().try_into()?),
+        window_seconds,
+    })
+}
+
+pub fn query_price_history(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<PriceHistoryResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as u64;
+    let count = OBSERVATION_COUNT
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    let start = match start_after {
+        Some(timestamp) => first_observation_after(deps.storage, count, timestamp)?,
+        None => count.saturating_sub(PRICE_HISTORY_SIZE),
+    };
+
+    let observations = (start..count.min(start + limit))
+        .map(|index| load_observation(deps.storage, index))
+        .collect::<StdResult<_>>()?;
+    Ok(PriceHistoryResponse { observations })
+}
+
+pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let Config {
+        buy_fee_bps,
+        sell_fee_bps,
+        treasury,
+        fee_splits,
+        unrefunded_reserve,
+        burn_from_payout,
+    } = CONFIG.load(deps.storage)?;
+    let PauseState { buys, sells } = PAUSED.may_load(deps.storage)?.unwrap_or_default();
+
+    Ok(ConfigResponse {
+        buy_fee_bps,
+        sell_fee_bps,
+        treasury,
+        fee_splits,
+        unrefunded_reserve,
+        burn_from_payout,
+        admin: ADMIN.may_load(deps.storage)?,
+        pending_admin: PENDING_ADMIN.may_load(deps.storage)?,
+        buys_paused: buys,
+        sells_paused: sells,
+    })
+}
+
+pub fn query_sale_phase(deps: Deps) -> StdResult<SalePhaseResponse> {
+    let state = CURVE_STATE.load(deps.storage)?;
+    Ok(SalePhaseResponse {
+        phase: state.phase,
+        supply_cap: state.supply_cap,
+    })
+}
+
+pub fn query_hatch_allowlisted(deps: Deps, address: String) -> StdResult<HatchAllowlistedResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    Ok(HatchAllowlistedResponse {
+        allowlisted: HATCH_ALLOWLIST.has(deps.storage, &address),
+    })
+}
+
+pub fn query_funding_pool(deps: Deps) -> StdResult<FundingPoolResponse> {
+    let state = CURVE_STATE.load(deps.storage)?;
+    Ok(FundingPoolResponse {
+        balance: state.funding_pool,
+        funding: state.funding,
+    })
+}
+
+pub fn query_simulate_buy(
+    deps: Deps,
+    curve_fn: CurveFn,
+    reserve_amount: Uint128,
+) -> StdResult<SimulationResponse> {
+    let state = CURVE_STATE.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
+    let curve = curve_fn(state.decimals);
+
+    let trade = calc_buy(&state, &config, curve.as_ref(), reserve_amount)?;
+    let spot_price = curve.spot_price(state.supply + trade.supply)?;
+    simulation_response(state.decimals, trade, spot_price)
+}
+
+pub fn query_simulate_sell(
+    deps: Deps,
+    curve_fn: CurveFn,
+    supply_amount: Uint128,
+) -> StdResult<SimulationResponse> {
+    let state = CURVE_STATE.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
+    let curve = curve_fn(state.decimals);
+
+    let trade = calc_sell(&state, &config, curve.as_ref(), supply_amount)?;
+    let spot_price = curve.spot_price(state.supply - trade.supply)?;
+    simulation_response(state.decimals, trade, spot_price)
+}
+
+/// Grosses the reserve needed for `supply_amount` tokens up by the buy fee and simulates
+/// a buy with it, so the response reflects curve rounding as well
+pub fn query_reverse_simulate_buy(
+    deps: Deps,
+    curve_fn: CurveFn,
+    supply_amount: Uint128,
+) -> StdResult<SimulationResponse> {
+    let state = CURVE_STATE.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
+    let curve = curve_fn(state.decimals);
+
+    let new_supply = state
+        .supply
+        .checked_add(supply_amount)
+        .map_err(StdError::overflow)?;
+    let payment = gross_up(
+        buy_cost(&state, curve.as_ref(), new_supply)?,
+        config.buy_fee_bps,
+    )?;
+
+    let trade = calc_buy(&state, &config, curve.as_ref(), payment)?;
+    let spot_price = curve.spot_price(state.supply + trade.supply)?;
+    simulation_response(state.decimals, trade, spot_price)
+}
+
+/// Finds how many tokens must be burned to receive at least `reserve_amount` after the sell fee
+/// and exit tribute.
+/// The share of the donated reserve is not taken into account, so it may burn more than needed
+pub fn query_reverse_simulate_sell(
+    deps: Deps,
+    curve_fn: CurveFn,
+    reserve_amount: Uint128,
+) -> StdResult<SimulationResponse> {
+    let state = CURVE_STATE.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
+    let curve = curve_fn(state.decimals);
+
+    let gross = gross_up(
+        reserve_amount,
+        config.sell_fee_bps + exit_tribute_bps(&state),
+    )?;
+    let new_reserve = state
+        .reserve
+        .checked_sub(gross)
+        .map_err(StdError::overflow)?;
+    let amount = state.supply.saturating_sub(curve.supply(new_reserve)?);
+
+    let trade = calc_sell(&state, &config, curve.as_ref(), amount)?;
+    let spot_price = curve.spot_price(state.supply - trade.supply)?;
+    simulation_response(state.decimals, trade, spot_price)
+}
+
+/// Smallest amount that still leaves `net` after taking a fee of `fee_bps` rounded down
+fn gross_up(net: Uint128, fee_bps: u64) -> StdResult<Uint128> {
+    let net_bps = FEE_BPS_DENOMINATOR
+        .checked_sub(fee_bps)
+        .filter(|bps| *bps > 0)
+        .ok_or_else(|| StdError::generic_err("fee leaves nothing to trade with"))?;
+    let gross = net
+        .checked_mul(Uint128::from(FEE_BPS_DENOMINATOR))?
+        .checked_add(Uint128::from(net_bps - 1))?;
+    Ok(gross / Uint128::from(net_bps))
+}
+
+fn simulation_response(
+    decimals: DecimalPlaces,
+    trade: TradeAmounts,
+    spot_price: Decimal,
+) -> StdResult<SimulationResponse> {
+    // price is reserve / supply in whole tokens, like the curve's spot price
+    let average_price = if trade.supply.is_zero() {
+        Decimal::zero()
+    } else {
+        let reserve = trade
+            .reserve
+            .checked_mul(Uint128::new(10u128.pow(decimals.supply)))
+            .map_err(StdError::overflow)?;
+        let supply = trade
+            .supply
+            .checked_mul(Uint128::new(10u128.pow(decimals.reserve)))
+            .map_err(StdError::overflow)?;
+        Decimal::checked_from_ratio(reserve, supply)
+            .map_err(|e| StdError::generic_err(e.to_string()))?
+    };
+
+    Ok(SimulationResponse {
+        reserve_amount: trade.reserve,
+        supply_amount: trade.supply,
+        fee: trade.fee,
+        funding: trade.funding,
+        average_price,
+        spot_price,
+    })
+}
+
+// this is poor mans "skip" flag
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::{
+        CurveType, FeeSplit, FundingParams, HatchParams, PiecewiseSegment, TradeSide,
+    };
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MOCK_CONTRACT_ADDR};
+    use cosmwasm_std::{
+        coin, coins, BankMsg, Decimal, OverflowError, OverflowOperation, StdError, SubMsg, WasmMsg,
+    };
+    use cw20::Expiration;
+    use cw20::{
+        AllowanceResponse, Cw20ExecuteMsg, EmbeddedLogo, Logo, LogoInfo, MinterResponse,
+        SpenderAllowanceInfo,
+    };
+    use cw20_base::msg::InstantiateMarketingInfo;
+    use cw20_base::state::BALANCES;
+    use cw_utils::PaymentError;
+    use std::str::FromStr;
+
+    const DENOM: &str = "satoshi";
+    const CREATOR: &str = "creator";
+    const INVESTOR: &str = "investor";
+    const BUYER: &str = "buyer";
+    const TREASURY: &str = "treasury";
+    const STABLE: &str = "stable";
+    const BENEFICIARY: &str = "beneficiary";
+
+    fn default_instantiate(
+        decimals: u8,
+        reserve_decimals: u8,
+        curve_type: CurveType,
+    ) -> InstantiateMsg {
+        InstantiateMsg {
+            name: "Bonded".to_string(),
+            symbol: "EPOXY".to_string(),
+            decimals,
+            reserve_asset: ReserveAsset::Native {
+                denom: DENOM.to_string(),
+            },
+            reserve_decimals,
+            curve_type,
+            buy_fee_bps: 0,
+            sell_fee_bps: 0,
+            treasury: Addr::unchecked(TREASURY),
+            fee_splits: vec![],
+            unrefunded_reserve: UnrefundedReserve::Donate,
+            burn_from_payout: BurnFromPayout::Spender,
+            supply_cap: None,
+            hatch: None,
+            funding: None,
+            admin: None,
+            marketing: None,
+        }
+    }
+
+    fn get_balance<U: Into<String>>(deps: Deps, addr: U) -> Uint128 {
+        query_balance(deps, addr.into()).unwrap().balance
+    }
+
+    fn setup_test(deps: DepsMut, decimals: u8, reserve_decimals: u8, curve_type: CurveType) {
+        let msg = default_instantiate(decimals, reserve_decimals, curve_type);
+        setup_test_with(deps, msg);
+    }
+
+    fn setup_test_with(deps: DepsMut, msg: InstantiateMsg) {
+        // this matches `linear_curve` test case from curves.rs
+        let creator = String::from(CREATOR);
+        let info = mock_info(&creator, &[]);
+
+        // make sure we can instantiate with this
+        let res = instantiate(deps, mock_env(), info, msg).unwrap();
+        assert_eq!(0, res.messages.len());
+    }
+
+    #[test]
+    fn proper_instantiation() {
+        let mut deps = mock_dependencies();
+
+        // this matches `linear_curve` test case from curves.rs
+        let creator = String::from("creator");
+        let curve_type = CurveType::SquareRoot {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        let msg = default_instantiate(2, 8, curve_type.clone());
+        let info = mock_info(&creator, &[]);
+
+        // make sure we can instantiate with this
+        let res = instantiate(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+        assert_eq!(0, res.messages.len());
+
+        // token info is proper
+        let token = query_token_info(deps.as_ref()).unwrap();
+        assert_eq!(&token.name, &msg.name);
+        assert_eq!(&token.symbol, &msg.symbol);
+        assert_eq!(token.decimals, 2);
+        assert_eq!(token.total_supply, Uint128::zero());
+
+        // curve state is sensible
+        let state = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
+        assert_eq!(state.reserve, Uint128::zero());
+        assert_eq!(state.supply, Uint128::zero());
+        assert_eq!(
+            state.reserve_asset,
+            ReserveAsset::Native {
+                denom: DENOM.to_string()
+            }
+        );
+        // spot price 0 as supply is 0
+        assert_eq!(state.spot_price, Decimal::zero());
+
+        // curve type is stored properly
+        let curve = CURVE_TYPE.load(&deps.storage).unwrap();
+        assert_eq!(curve_type, curve);
+
+        // no balance
+        assert_eq!(get_balance(deps.as_ref(), &creator), Uint128::zero());
+    }
+
+    #[test]
+    fn buy_issues_tokens() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        setup_test(deps.as_mut(), 2, 8, curve_type.clone());
+
+        // succeeds with proper token (5 BTC = 5*10^8 satoshi)
+        let info = mock_info(INVESTOR, &coins(500_000_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, buy.clone()).unwrap();
+
+        // bob got 1000 EPOXY (10.00)
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(1000));
+        assert_eq!(get_balance(deps.as_ref(), BUYER), Uint128::zero());
+
+        // send them all to buyer
+        let info = mock_info(INVESTOR, &[]);
+        let send = ExecuteMsg::Transfer {
+            recipient: BUYER.into(),
+            amount: Uint128::new(1000),
+        };
+        execute(deps.as_mut(), mock_env(), info, send).unwrap();
+
+        // ensure balances updated
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::zero());
+        assert_eq!(get_balance(deps.as_ref(), BUYER), Uint128::new(1000));
+
+        // second stake needs more to get next 1000 EPOXY
+        let info = mock_info(INVESTOR, &coins(1_500_000_000, DENOM));
+        execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+
+        // ensure balances updated
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(1000));
+        assert_eq!(get_balance(deps.as_ref(), BUYER), Uint128::new(1000));
+
+        // check curve info updated
+        let curve = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
+        assert_eq!(curve.reserve, Uint128::new(2_000_000_000));
+        assert_eq!(curve.supply, Uint128::new(2000));
+        assert_eq!(curve.spot_price, Decimal::percent(200));
+
+        // check token info updated
+        let token = query_token_info(deps.as_ref()).unwrap();
+        assert_eq!(token.decimals, 2);
+        assert_eq!(token.total_supply, Uint128::new(2000));
+    }
+
+    #[test]
+    fn buy_exp_tokens() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Exponential {
+            slope: Uint128::new(55_000_000u128),
+            scale: 0,
+        };
+        let msg = InstantiateMsg {
+            buy_fee_bps: 1000,
+            ..default_instantiate(2, 6, curve_type.clone())
+        };
+        setup_test_with(deps.as_mut(), msg);
+
+        // succeeds with proper token (61.12 BTC - 6.112 BTC(Fee) = 55*10^6 satoshi)
+        let info = mock_info(INVESTOR, &coins(61_120_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, buy.clone()).unwrap();
+
+        // bob got 100 EPOXY (1.0)
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(100));
+        assert_eq!(get_balance(deps.as_ref(), BUYER), Uint128::zero());
+
+        // send them all to buyer
+        let info = mock_info(INVESTOR, &[]);
+        let send = ExecuteMsg::Transfer {
+            recipient: BUYER.into(),
+            amount: Uint128::new(100),
+        };
+        execute(deps.as_mut(), mock_env(), info, send).unwrap();
+
+        // ensure balances updated
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::zero());
+        assert_eq!(get_balance(deps.as_ref(), BUYER), Uint128::new(100));
+
+        // second stake needs more to get next 100 EPOXY
+        let info = mock_info(INVESTOR, &coins(111_737_213, DENOM));
+        execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+
+        // ensure balances updated
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(100));
+        assert_eq!(get_balance(deps.as_ref(), BUYER), Uint128::new(100));
+
+        // check curve info updated
+        let curve = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
+        assert_eq!(curve.reserve, Uint128::new(155_571_492));
+        assert_eq!(curve.supply, Uint128::new(200));
+        assert_eq!(
+            curve.spot_price,
+            Decimal::from_str("285.788383248864753345").unwrap()
+        );
+
+        // check token info updated
+        let token = query_token_info(deps.as_ref()).unwrap();
+        assert_eq!(token.decimals, 2);
+        assert_eq!(token.total_supply, Uint128::new(200));
+    }
+
+    #[test]
+    fn bonding_fails_with_wrong_denom() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        setup_test(deps.as_mut(), 2, 8, curve_type);
+
+        // fails when no tokens sent
+        let info = mock_info(INVESTOR, &[]);
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, buy.clone()).unwrap_err();
+        assert_eq!(err, PaymentError::NoFunds {}.into());
+
+        // fails when wrong tokens sent                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                              
+        let info = mock_info(INVESTOR, &coins(1234567, "wei"));
+        let err = execute(deps.as_mut(), mock_env(), info, buy.clone()).unwrap_err();
+        assert_eq!(err, PaymentError::MissingDenom(DENOM.into()).into());
+
+        // fails when too many tokens sent
+        let info = mock_info(INVESTOR, &[coin(3400022, DENOM), coin(1234567, "wei")]);
+        let err = execute(deps.as_mut(), mock_env(), info, buy).unwrap_err();
+        assert_eq!(err, PaymentError::MultipleDenoms {}.into());
+    }
+
+    #[test]
+    fn burning_sends_reserve() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        setup_test(deps.as_mut(), 2, 8, curve_type.clone());
+
+        // succeeds with proper token (20 BTC = 20*10^8 satoshi)
+        let info = mock_info(INVESTOR, &coins(2_000_000_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+
+        // bob got 2000 EPOXY (20.00)
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(2000));
+
+        // cannot burn too much
+        let info = mock_info(INVESTOR, &[]);
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(3000),
+            refund: Some(true),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, burn).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Base(cw20_base::ContractError::Std(StdError::overflow(
+                OverflowError::new(OverflowOperation::Sub, 2000, 3000)
+            )))
+        );
+
+        // burn 1000 EPOXY to get back 15BTC (*10^8)
+        let info = mock_info(INVESTOR, &[]);
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(1000),
+            refund: Some(true),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, burn).unwrap();
+
+        // balance is lower
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(1000));
+
+        // ensure we got our money back
+        assert_eq!(1, res.messages.len());
+        assert_eq!(
+            &res.messages[0],
+            &SubMsg::new(BankMsg::Send {
+                to_address: INVESTOR.into(),
+                amount: coins(1_500_000_000, DENOM),
+            })
+        );
+
+        // check curve info updated
+        let curve = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
+        assert_eq!(curve.reserve, Uint128::new(500_000_000));
+        assert_eq!(curve.supply, Uint128::new(1000));
+        assert_eq!(curve.spot_price, Decimal::percent(100));
+
+        // check token info updated
+        let token = query_token_info(deps.as_ref()).unwrap();
+        assert_eq!(token.decimals, 2);
+        assert_eq!(token.total_supply, Uint128::new(1000));
+    }
+
+    #[test]
+    fn burning_exp_sends_reserve() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Exponential {
+            slope: Uint128::new(55_000_000u128),
+            scale: 0,
+        };
+        let msg = InstantiateMsg {
+            buy_fee_bps: 1000,
+            ..default_instantiate(2, 6, curve_type.clone())
+        };
+        setup_test_with(deps.as_mut(), msg);
+
+        // succeeds with proper token (156 BTC = 156*10^6 satoshi)
+        let info = mock_info(INVESTOR, &coins(173_000_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+
+        // bob got 200 EPOXY (2.0)
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(200));
+
+        // cannot burn too much
+        let info = mock_info(INVESTOR, &[]);
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(300),
+            refund: Some(true),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, burn).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Base(cw20_base::ContractError::Std(StdError::overflow(
+                OverflowError::new(OverflowOperation::Sub, 200, 300)
+            )))
+        );
+
+        // burn 100 EPOXY to get back 55 BTC (*10^6)
+        let info = mock_info(INVESTOR, &[]);
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(100),
+            refund: Some(true),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, burn).unwrap();
+
+        // balance is lower
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(100));
+
+        // ensure we got our money back
+        assert_eq!(1, res.messages.len());
+        assert_eq!(
+            &res.messages[0],
+            &SubMsg::new(BankMsg::Send {
+                to_address: INVESTOR.into(),
+                amount: coins(100_700_000, DENOM),
+            })
+        );
+
+        // check curve info updated
+        let curve = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
+        assert_eq!(curve.reserve, Uint128::new(55_000_000));
+        assert_eq!(curve.supply, Uint128::new(100));
+
+        // check token info updated
+        let token = query_token_info(deps.as_ref()).unwrap();
+        assert_eq!(token.decimals, 2);
+        assert_eq!(token.total_supply, Uint128::new(100));
+    }
+
+    #[test]
+    fn simulations_match_execution() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        let msg = InstantiateMsg {
+            buy_fee_bps: 1000,
+            ..default_instantiate(2, 8, curve_type.clone())
+        };
+        setup_test_with(deps.as_mut(), msg);
+
+        // 10% fee leaves 18 BTC for the curve, which buys 18.97 EPOXY
+        let sim = query_simulate_buy(
+            deps.as_ref(),
+            curve_type.to_curve_fn(),
+            Uint128::new(2_000_000_000),
+        )
+        .unwrap();
+        assert_eq!(sim.reserve_amount, Uint128::new(2_000_000_000));
+        assert_eq!(sim.fee, Uint128::new(200_000_000));
+        assert_eq!(sim.supply_amount, Uint128::new(1897));
+        assert_eq!(sim.spot_price, Decimal::permille(1897));
+        // 20 BTC / 18.97 EPOXY
+        assert_eq!(
+            sim.average_price,
+            Decimal::from_ratio(2_000_000_000u128, 18_970_000_000u128 / 10)
+        );
+
+        let info = mock_info(INVESTOR, &coins(2_000_000_000, DENOM));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Buy {
+                recipient: None,
+                min_tokens_out: None,
+                deadline: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), sim.supply_amount);
+
+        // selling 897 EPOXY goes back down to 10 EPOXY on the curve (5 BTC reserve)
+        let sim = query_simulate_sell(deps.as_ref(), curve_type.to_curve_fn(), Uint128::new(897))
+            .unwrap();
+        let curve = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
+        assert_eq!(
+            sim.reserve_amount,
+            curve.reserve - Uint128::new(500_000_000)
+        );
+        assert_eq!(sim.fee, Uint128::zero());
+        assert_eq!(sim.spot_price, Decimal::percent(100));
+
+        // the reverse sell finds the same amount of tokens
+        let reverse = query_reverse_simulate_sell(
+            deps.as_ref(),
+            curve_type.to_curve_fn(),
+            sim.reserve_amount,
+        )
+        .unwrap();
+        assert_eq!(reverse, sim);
+
+        let info = mock_info(INVESTOR, &[]);
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(897),
+            refund: Some(true),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, burn).unwrap();
+        assert_eq!(
+            &res.messages[0],
+            &SubMsg::new(BankMsg::Send {
+                to_address: INVESTOR.into(),
+                amount: coins(sim.reserve_amount.u128(), DENOM),
+            })
+        );
+
+        // buying back up to 20 EPOXY costs 15 BTC plus fee on the curve
+        let reverse =
+            query_reverse_simulate_buy(deps.as_ref(), curve_type.to_curve_fn(), Uint128::new(1000))
+                .unwrap();
+        assert_eq!(reverse.supply_amount, Uint128::new(1000));
+        assert_eq!(reverse.reserve_amount, Uint128::new(1_666_666_667));
+        assert_eq!(reverse.fee, Uint128::new(166_666_666));
+        assert_eq!(reverse.spot_price, Decimal::percent(200));
+    }
+
+    #[test]
+    fn slippage_and_deadline_protection() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        let msg = InstantiateMsg {
+            buy_fee_bps: 1000,
+            ..default_instantiate(2, 8, curve_type)
+        };
+        setup_test_with(deps.as_mut(), msg);
+
+        // 20 BTC minus 10% fee only buys 18.97 EPOXY
+        let info = mock_info(INVESTOR, &coins(2_000_000_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: Some(Uint128::new(2000)),
+            deadline: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), buy).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::MinTokensOut {
+                min_tokens_out: Uint128::new(2000),
+                tokens_out: Uint128::new(1897),
+            }
+        );
+
+        // expired deadline
+        let env = mock_env();
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: Some(Expiration::AtHeight(env.block.height - 1)),
+        };
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), buy).unwrap_err();
+        assert_eq!(err, ContractError::DeadlineExpired {});
+
+        // both limits met
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: Some(Uint128::new(1897)),
+            deadline: Some(Expiration::AtTime(env.block.time.plus_seconds(60))),
+        };
+        execute(deps.as_mut(), env.clone(), info, buy).unwrap();
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(1897));
+
+        let info = mock_info(INVESTOR, &[]);
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(897),
+            refund: Some(true),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: Some(Expiration::AtHeight(env.block.height)),
+        };
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), burn).unwrap_err();
+        assert_eq!(err, ContractError::DeadlineExpired {});
+
+        // burning back to 10 EPOXY releases exactly 13 BTC
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(897),
+            refund: Some(true),
+            recipient: None,
+            min_reserve_out: Some(Uint128::new(1_300_000_000)),
+            deadline: Some(Expiration::AtHeight(env.block.height + 1)),
+        };
+        execute(deps.as_mut(), env.clone(), info.clone(), burn).unwrap();
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(1000));
+
+        // burning down to 5 EPOXY releases less than 4 BTC
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(500),
+            refund: Some(true),
+            recipient: None,
+            min_reserve_out: Some(Uint128::new(400_000_000)),
+            deadline: None,
+        };
+        let err = execute(deps.as_mut(), env, info, burn).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::MinReserveOut {
+                min_reserve_out: Uint128::new(400_000_000),
+                reserve_out: Uint128::new(375_000_000),
+            }
+        );
+    }
+
+    #[test]
+    fn fees_are_split_between_recipients() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        let msg = InstantiateMsg {
+            buy_fee_bps: 1000,
+            sell_fee_bps: 500,
+            fee_splits: vec![
+                FeeSplit {
+                    recipient: FeeRecipient::Treasury,
+                    weight: 2,
+                },
+                FeeSplit {
+                    recipient: FeeRecipient::Address {
+                        address: Addr::unchecked(CREATOR),
+                    },
+                    weight: 1,
+                },
+                FeeSplit {
+                    recipient: FeeRecipient::Burn,
+                    weight: 1,
+                },
+            ],
+            ..default_instantiate(2, 8, curve_type.clone())
+        };
+        setup_test_with(deps.as_mut(), msg);
+
+        // 10% of 20 BTC is split 1:1:2 between creator, burn and treasury
+        let info = mock_info(INVESTOR, &coins(2_000_000_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: CREATOR.into(),
+                    amount: coins(50_000_000, DENOM),
+                }),
+                SubMsg::new(BankMsg::Burn {
+                    amount: coins(50_000_000, DENOM),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: TREASURY.into(),
+                    amount: coins(100_000_000, DENOM),
+                }),
+            ]
+        );
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(1897));
+
+        // selling back to 10 EPOXY releases 13 BTC, 5% of which is the fee
+        let sim = query_simulate_sell(deps.as_ref(), curve_type.to_curve_fn(), Uint128::new(897))
+            .unwrap();
+        assert_eq!(sim.reserve_amount, Uint128::new(1_235_000_000));
+        assert_eq!(sim.fee, Uint128::new(65_000_000));
+        let reverse = query_reverse_simulate_sell(
+            deps.as_ref(),
+            curve_type.to_curve_fn(),
+            sim.reserve_amount,
+        )
+        .unwrap();
+        assert_eq!(reverse, sim);
+
+        let info = mock_info(INVESTOR, &[]);
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(897),
+            refund: Some(true),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, burn).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: CREATOR.into(),
+                    amount: coins(16_250_000, DENOM),
+                }),
+                SubMsg::new(BankMsg::Burn {
+                    amount: coins(16_250_000, DENOM),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: TREASURY.into(),
+                    amount: coins(32_500_000, DENOM),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: INVESTOR.into(),
+                    amount: coins(1_235_000_000, DENOM),
+                }),
+            ]
+        );
+
+        // the fee leaves the reserve as well
+        let curve = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
+        assert_eq!(curve.reserve, Uint128::new(500_000_000));
+        assert_eq!(curve.supply, Uint128::new(1000));
+    }
+
+    #[test]
+    fn zero_fees_send_nothing() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        setup_test(deps.as_mut(), 2, 8, curve_type);
+
+        let info = mock_info(INVESTOR, &coins(500_000_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+        assert_eq!(res.messages, vec![]);
+    }
+
+    #[test]
+    fn dust_sells_send_nothing() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Constant {
+            value: Uint128::new(1),
+            scale: 0,
+        };
+        let msg = InstantiateMsg {
+            unrefunded_reserve: UnrefundedReserve::Treasury,
+            ..default_instantiate(8, 2, curve_type)
+        };
+        setup_test_with(deps.as_mut(), msg);
+
+        let info = mock_info(INVESTOR, &coins(100, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+        assert_eq!(
+            get_balance(deps.as_ref(), INVESTOR),
+            Uint128::new(100_000_000)
+        );
+
+        // a single unit is worth less than the smallest reserve unit, the first burn only pays
+        // out because the remaining reserve is rounded down
+        let burn = |refund: Option<bool>| ExecuteMsg::Burn {
+            amount: Uint128::new(1),
+            refund,
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let info = mock_info(INVESTOR, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), burn(Some(true))).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        for refund in [Some(true), None] {
+            let res = execute(deps.as_mut(), mock_env(), info.clone(), burn(refund)).unwrap();
+            assert_eq!(res.messages, vec![]);
+        }
+        assert_eq!(
+            get_balance(deps.as_ref(), INVESTOR),
+            Uint128::new(99_999_997)
+        );
+    }
+
+    #[test]
+    fn buys_work_after_everything_is_burned() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        setup_test(deps.as_mut(), 2, 8, curve_type);
+        let buy = || ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        let info = mock_info(INVESTOR, &coins(2_000_000_000, DENOM));
+        execute(deps.as_mut(), mock_env(), info.clone(), buy()).unwrap();
+
+        // burning the whole supply without a refund leaves the reserve to nobody
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(2000),
+            refund: None,
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(INVESTOR, &[]), burn).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: TREASURY.into(),
+                amount: coins(2_000_000_000, DENOM),
+            })
+        );
+        let state = CURVE_STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.supply, Uint128::zero());
+        assert_eq!(state.reserve, Uint128::zero());
+        assert_eq!(state.donated_reserve, Uint128::zero());
+        execute(deps.as_mut(), mock_env(), info.clone(), buy()).unwrap();
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(2000));
+
+        // a contract already left with only donated reserve still takes buys, the buyers own it
+        let mut state = CURVE_STATE.load(&deps.storage).unwrap();
+        state.supply = Uint128::zero();
+        state.reserve = Uint128::zero();
+        state.donated_reserve = Uint128::new(500_000_000);
+        CURVE_STATE.save(deps.as_mut().storage, &state).unwrap();
+        execute(deps.as_mut(), mock_env(), info, buy()).unwrap();
+        let state = CURVE_STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.supply, Uint128::new(2000));
+        assert_eq!(state.reserve, Uint128::new(2_000_000_000));
+        assert_eq!(state.donated_reserve, Uint128::new(500_000_000));
+    }
+
+    #[test]
+    fn sells_work_with_reserve_below_the_curve() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::SquareRoot {
+            slope: Uint128::new(35),
+            scale: 2,
+        };
+        setup_test(deps.as_mut(), 6, 2, curve_type);
+        let info = mock_info(INVESTOR, &coins(84_058, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+
+        // as left behind by the truncated roots of 0.1
+        let mut state = CURVE_STATE.load(&deps.storage).unwrap();
+        state.reserve -= Uint128::new(2);
+        CURVE_STATE.save(deps.as_mut().storage, &state).unwrap();
+
+        let burn = |amount: u128| ExecuteMsg::Burn {
+            amount: Uint128::new(amount),
+            refund: Some(true),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let info = mock_info(INVESTOR, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), burn(1)).unwrap();
+        assert_eq!(res.messages, vec![]);
+        // selling everything still pays out the whole reserve
+        let res = execute(deps.as_mut(), mock_env(), info, burn(235_000_169)).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: INVESTOR.into(),
+                amount: coins(84_056, DENOM),
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_fees_are_rejected() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        let info = mock_info(CREATOR, &[]);
+
+        let msg = InstantiateMsg {
+            sell_fee_bps: 10_001,
+            ..default_instantiate(2, 8, curve_type.clone())
+        };
+        let err = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidFee { fee_bps: 10_001 });
+
+        let msg = InstantiateMsg {
+            fee_splits: vec![FeeSplit {
+                recipient: FeeRecipient::Burn,
+                weight: 0,
+            }],
+            ..default_instantiate(2, 8, curve_type)
+        };
+        let err = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidFeeSplits {});
+    }
+
+    #[test]
+    fn invalid_curves_are_rejected() {
+        let mut deps = mock_dependencies();
+        let info = mock_info(CREATOR, &[]);
+
+        let curve_type = CurveType::Power {
+            slope: Uint128::new(1),
+            scale: 1,
+            exponent_num: 1,
+            exponent_den: 0,
+        };
+        let msg = default_instantiate(2, 8, curve_type);
+        let err = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidCurve { .. }));
+
+        let segment = |start: u128| PiecewiseSegment {
+            start: Uint128::new(start),
+            price: Uint128::new(1),
+            slope: Uint128::zero(),
+            scale: 0,
+        };
+        let curve_type = CurveType::Piecewise {
+            segments: vec![segment(0), segment(500), segment(500)],
+        };
+        let msg = default_instantiate(2, 8, curve_type);
+        let err = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidCurve {
+                reason: "segments must be sorted by start".to_string()
+            }
+        );
+
+        let curve_type = CurveType::Piecewise {
+            segments: vec![segment(0), segment(500)],
+        };
+        let msg = default_instantiate(2, 8, curve_type);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
+    #[test]
+    fn invalid_instantiate_params_are_rejected() {
+        let linear = |slope: u128, scale: u32| CurveType::Linear {
+            slope: Uint128::new(slope),
+            scale,
+        };
+        let valid = || default_instantiate(2, 8, linear(1, 1));
+        let cases = vec![
+            (
+                InstantiateMsg {
+                    name: "Bo".to_string(),
+                    ..valid()
+                },
+                ContractError::InvalidName {},
+            ),
+            (
+                InstantiateMsg {
+                    name: "B".repeat(51),
+                    ..valid()
+                },
+                ContractError::InvalidName {},
+            ),
+            (
+                InstantiateMsg {
+                    symbol: "EP".to_string(),
+                    ..valid()
+                },
+                ContractError::InvalidSymbol {},
+            ),
+            (
+                InstantiateMsg {
+                    symbol: "EPOXY1".to_string(),
+                    ..valid()
+                },
+                ContractError::InvalidSymbol {},
+            ),
+            (
+                default_instantiate(19, 8, linear(1, 1)),
+                ContractError::InvalidDecimals { decimals: 19 },
+            ),
+            (
+                default_instantiate(2, 255, linear(1, 1)),
+                ContractError::InvalidReserveDecimals { decimals: 255 },
+            ),
+            (
+                InstantiateMsg {
+                    reserve_asset: ReserveAsset::Native {
+                        denom: "".to_string(),
+                    },
+                    ..valid()
+                },
+                ContractError::InvalidReserveDenom {},
+            ),
+            (
+                InstantiateMsg {
+                    supply_cap: Some(Uint128::zero()),
+                    ..valid()
+                },
+                ContractError::InvalidSupplyCap {},
+            ),
+            (
+                InstantiateMsg {
+                    buy_fee_bps: 10_001,
+                    ..valid()
+                },
+                ContractError::InvalidFee { fee_bps: 10_001 },
+            ),
+            (
+                default_instantiate(2, 8, linear(0, 1)),
+                ContractError::InvalidCurveSlope {},
+            ),
+            (
+                default_instantiate(
+                    2,
+                    8,
+                    CurveType::Constant {
+                        value: Uint128::zero(),
+                        scale: 1,
+                    },
+                ),
+                ContractError::InvalidCurveSlope {},
+            ),
+            (
+                default_instantiate(2, 8, linear(1, 19)),
+                ContractError::InvalidCurveScale { scale: 19 },
+            ),
+        ];
+        for (msg, expected) in cases {
+            let err = instantiate(
+                mock_dependencies().as_mut(),
+                mock_env(),
+                mock_info(CREATOR, &[]),
+                msg,
+            )
+            .unwrap_err();
+            assert_eq!(err, expected);
+        }
+
+        // the limits themselves are fine
+        let msg = InstantiateMsg {
+            name: "B".repeat(50),
+            symbol: "EPOXY-SHIRTS".to_string(),
+            ..default_instantiate(18, 18, linear(1, 18))
+        };
+        setup_test_with(mock_dependencies().as_mut(), msg);
+    }
+
+    #[test]
+    fn huge_trades_error_instead_of_panicking() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        setup_test(deps.as_mut(), 2, 8, curve_type.clone());
+
+        // any u128 payment can be priced
+        let info = mock_info(INVESTOR, &coins(u128::MAX, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+
+        // but a supply the curve can't price fails the trade instead of the contract
+        let mut state = CURVE_STATE.load(&deps.storage).unwrap();
+        state.supply = Uint128::MAX;
+        CURVE_STATE.save(&mut deps.storage, &state).unwrap();
+        let err = query_simulate_sell(deps.as_ref(), curve_type.to_curve_fn(), Uint128::new(1))
+            .unwrap_err();
+        assert!(matches!(err, StdError::Overflow { .. }));
+        query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap_err();
+    }
+
+    #[test]
+    fn twap_averages_recorded_prices() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        setup_test(deps.as_mut(), 2, 8, curve_type.clone());
+        let curve = curve_type.to_curve_fn()(DecimalPlaces::new(2, 8));
+        let price_at = |supply: u128| curve.spot_price(Uint128::new(supply)).unwrap();
+        let start = mock_env().block.time.seconds();
+        let mut env = mock_env();
+
+        // no trades, no history
+        query_twap(deps.as_ref(), env.clone(), 1).unwrap_err();
+
+        // buy 20 EPOXY
+        let info = mock_info(INVESTOR, &coins(2_000_000_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        execute(deps.as_mut(), env.clone(), info, buy).unwrap();
+
+        // sell most of it back 100 seconds later, in two trades of the same block
+        env.block.time = env.block.time.plus_seconds(100);
+        let info = mock_info(INVESTOR, &[]);
+        for amount in [1000, 500] {
+            let burn = ExecuteMsg::Burn {
+                amount: Uint128::new(amount),
+                refund: Some(true),
+                recipient: None,
+                min_reserve_out: None,
+                deadline: None,
+            };
+            execute(deps.as_mut(), env.clone(), info.clone(), burn).unwrap();
+        }
+
+        // only the last price of a block is kept
+        let history = query_price_history(deps.as_ref(), None, None).unwrap();
+        assert_eq!(
+            history.observations,
+            vec![
+                PriceObservation {
+                    timestamp: start,
+                    spot_price: price_at(2000),
+                    cumulative_price: Decimal256::zero(),
+                },
+                PriceObservation {
+                    timestamp: start + 100,
+                    spot_price: price_at(500),
+                    cumulative_price: Decimal256::from(price_at(2000))
+                        * Decimal256::from_ratio(100u8, 1u8),
+                },
+            ]
+        );
+        let page = query_price_history(deps.as_ref(), Some(start), Some(1)).unwrap();
+        assert_eq!(page.observations, history.observations[1..]);
+
+        // 100 seconds at the 20 EPOXY price and 200 at the 5 EPOXY one average to the 10 EPOXY one
+        env.block.time = env.block.time.plus_seconds(200);
+        let twap = query_twap(deps.as_ref(), env.clone(), 300).unwrap();
+        assert_eq!(twap.twap, price_at(1000));
+        let twap = query_twap(deps.as_ref(), env.clone(), 200).unwrap();
+        assert_eq!(twap.twap, price_at(500));
+
+        // the window can't reach before the first trade
+        query_twap(deps.as_ref(), env.clone(), 301).unwrap_err();
+        query_twap(deps.as_ref(), env, 0).unwrap_err();
+    }
+
+    #[test]
+    fn price_history_drops_the_oldest_observations() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        setup_test(deps.as_mut(), 2, 8, curve_type.clone());
+        let curve = curve_type.to_curve_fn()(DecimalPlaces::new(2, 8));
+        let start = mock_env().block.time.seconds();
+        let mut env = mock_env();
+
+        // 300 blocks, 10 seconds apart, with the supply going up by 1 each
+        for supply in 1..=300u64 {
+            record_price(&mut deps.storage, &env, curve.as_ref(), supply.into()).unwrap();
+            env.block.time = env.block.time.plus_seconds(10);
+        }
+        let timestamp = |index: u64| start + index * 10;
+
+        // the first 44 are gone, the rest come oldest first
+        let history = query_price_history(deps.as_ref(), None, Some(MAX_LIMIT)).unwrap();
+        let timestamps: Vec<_> = history.observations.iter().map(|o| o.timestamp).collect();
+        assert_eq!(timestamps, (44..74).map(timestamp).collect::<Vec<_>>());
+        assert_eq!(
+            history.observations[0].spot_price,
+            curve.spot_price(Uint128::new(45)).unwrap()
+        );
+        // starting after an evicted one also begins at the oldest left
+        let page = query_price_history(deps.as_ref(), Some(timestamp(10)), None).unwrap();
+        assert_eq!(page.observations[..], history.observations[..10]);
+        let page = query_price_history(deps.as_ref(), Some(timestamp(44)), Some(1)).unwrap();
+        assert_eq!(page.observations[..], history.observations[1..2]);
+        let page = query_price_history(deps.as_ref(), Some(timestamp(290)), None).unwrap();
+        let timestamps: Vec<_> = page.observations.iter().map(|o| o.timestamp).collect();
+        assert_eq!(timestamps, (291..300).map(timestamp).collect::<Vec<_>>());
+
+        // 10 seconds each at supplies 45 to 300 average to 172.5 EPOXY, at a 0.1 slope
+        let twap = query_twap(deps.as_ref(), env.clone(), 2560).unwrap();
+        assert_eq!(twap.twap, Decimal::from_ratio(1725u32, 10000u32));
+        // a window starting before the oldest one left can't be priced
+        query_twap(deps.as_ref(), env, 2561).unwrap_err();
+    }
+
+    #[test]
+    fn admin_updates_config() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        setup_test(deps.as_mut(), 2, 8, curve_type);
 
-        // no balance
-        assert_eq!(get_balance(deps.as_ref(), &creator), Uint128::zero());
+        // instantiator is the admin
+        let config = query_config(deps.as_ref()).unwrap();
+        assert_eq!(config.admin, Some(Addr::unchecked(CREATOR)));
+        assert_eq!(config.pending_admin, None);
+        assert_eq!(config.buy_fee_bps, 0);
+
+        let update = ExecuteMsg::UpdateConfig {
+            buy_fee_bps: Some(500),
+            sell_fee_bps: None,
+            treasury: Some(Addr::unchecked(BUYER)),
+            fee_splits: None,
+            unrefunded_reserve: None,
+            burn_from_payout: None,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(INVESTOR, &[]),
+            update.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), update).unwrap();
+
+        let config = query_config(deps.as_ref()).unwrap();
+        assert_eq!(config.buy_fee_bps, 500);
+        assert_eq!(config.sell_fee_bps, 0);
+        assert_eq!(config.treasury, Addr::unchecked(BUYER));
+
+        // fees are still validated
+        let update = ExecuteMsg::UpdateConfig {
+            buy_fee_bps: None,
+            sell_fee_bps: Some(20_000),
+            treasury: None,
+            fee_splits: None,
+            unrefunded_reserve: None,
+            burn_from_payout: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), update).unwrap_err();
+        assert_eq!(err, ContractError::InvalidFee { fee_bps: 20_000 });
+
+        // admin transfer takes two steps
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(BUYER, &[]),
+            ExecuteMsg::AcceptAdmin {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NoPendingAdmin {});
+
+        let transfer = ExecuteMsg::TransferAdmin {
+            new_admin: BUYER.to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), transfer).unwrap();
+        let config = query_config(deps.as_ref()).unwrap();
+        assert_eq!(config.admin, Some(Addr::unchecked(CREATOR)));
+        assert_eq!(config.pending_admin, Some(Addr::unchecked(BUYER)));
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(INVESTOR, &[]),
+            ExecuteMsg::AcceptAdmin {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(BUYER, &[]),
+            ExecuteMsg::AcceptAdmin {},
+        )
+        .unwrap();
+
+        let config = query_config(deps.as_ref()).unwrap();
+        assert_eq!(config.admin, Some(Addr::unchecked(BUYER)));
+        assert_eq!(config.pending_admin, None);
+
+        // old admin lost its rights
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CREATOR, &[]),
+            ExecuteMsg::Pause { side: None },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
     }
 
     #[test]
-    fn buy_issues_tokens() {
+    fn pause_halts_each_side() {
         let mut deps = mock_dependencies();
         let curve_type = CurveType::Linear {
             slope: Uint128::new(1),
             scale: 1,
         };
-        setup_test(deps.as_mut(), 6, 6, curve_type.clone());
+        setup_test(deps.as_mut(), 2, 8, curve_type);
 
-        // succeeds with proper token (5 BTC = 5*10^8 satoshi)
+        let admin = mock_info(CREATOR, &[]);
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(100),
+            refund: Some(true),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let buy_info = mock_info(INVESTOR, &coins(500_000_000, DENOM));
+        let sell_info = mock_info(INVESTOR, &[]);
+
+        let pause = ExecuteMsg::Pause {
+            side: Some(TradeSide::Buy),
+        };
+        execute(deps.as_mut(), mock_env(), admin.clone(), pause).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), buy_info.clone(), buy.clone()).unwrap_err();
+        assert_eq!(err, ContractError::BuysPaused {});
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            admin.clone(),
+            ExecuteMsg::Unpause { side: None },
+        )
+        .unwrap();
+        execute(deps.as_mut(), mock_env(), buy_info.clone(), buy.clone()).unwrap();
+
+        let pause = ExecuteMsg::Pause {
+            side: Some(TradeSide::Sell),
+        };
+        execute(deps.as_mut(), mock_env(), admin.clone(), pause).unwrap();
+        let config = query_config(deps.as_ref()).unwrap();
+        assert!(!config.buys_paused);
+        assert!(config.sells_paused);
+
+        let err = execute(deps.as_mut(), mock_env(), sell_info.clone(), burn.clone()).unwrap_err();
+        assert_eq!(err, ContractError::SellsPaused {});
+        // buys still work
+        execute(deps.as_mut(), mock_env(), buy_info, buy).unwrap();
+
+        let unpause = ExecuteMsg::Unpause {
+            side: Some(TradeSide::Sell),
+        };
+        execute(deps.as_mut(), mock_env(), admin, unpause).unwrap();
+        execute(deps.as_mut(), mock_env(), sell_info, burn).unwrap();
+    }
+
+    #[test]
+    fn cw20_reserve_buy_and_sell() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        let msg = InstantiateMsg {
+            reserve_asset: ReserveAsset::Cw20 {
+                address: Addr::unchecked(STABLE),
+            },
+            buy_fee_bps: 1000,
+            ..default_instantiate(2, 8, curve_type.clone())
+        };
+        setup_test_with(deps.as_mut(), msg);
+
+        // native buys are not possible
         let info = mock_info(INVESTOR, &coins(500_000_000, DENOM));
-        let buy = ExecuteMsg::Buy {};
-        execute(deps.as_mut(), mock_env(), info, buy.clone()).unwrap();
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, buy).unwrap_err();
+        assert_eq!(err, ContractError::WrongReserveAsset {});
 
-        // bob got 1000 EPOXY (10.00)
-        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(1000));
-        assert_eq!(get_balance(deps.as_ref(), BUYER), Uint128::zero());
+        // only the reserve contract can call receive
+        let receive = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: INVESTOR.to_string(),
+            amount: Uint128::new(2_000_000_000),
+            msg: to_binary(&ReceiveMsg::Buy {
+                recipient: None,
+                min_tokens_out: None,
+                deadline: None,
+            })
+            .unwrap(),
+        });
+        let info = mock_info("fake", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, receive.clone()).unwrap_err();
+        assert_eq!(err, ContractError::WrongReserveAsset {});
 
-        // send them all to buyer
+        // the sender of the cw20 tokens gets the supply, the fee goes out as a cw20 transfer
+        let info = mock_info(STABLE, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, receive).unwrap();
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(1897));
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: STABLE.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: TREASURY.to_string(),
+                    amount: Uint128::new(200_000_000),
+                })
+                .unwrap(),
+                funds: vec![],
+            })]
+        );
+
+        // selling pays out with a cw20 transfer
         let info = mock_info(INVESTOR, &[]);
-        let send = ExecuteMsg::Transfer {
-            recipient: BUYER.into(),
-            amount: Uint128::new(1000),
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(897),
+            refund: Some(true),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
         };
-        execute(deps.as_mut(), mock_env(), info, send).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info, burn).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: STABLE.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: INVESTOR.to_string(),
+                    amount: Uint128::new(1_300_000_000),
+                })
+                .unwrap(),
+                funds: vec![],
+            })]
+        );
 
-        // ensure balances updated
+        let curve = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
+        assert_eq!(curve.reserve, Uint128::new(500_000_000));
+        assert_eq!(curve.supply, Uint128::new(1000));
+    }
+
+    #[test]
+    fn buy_for_recipient_and_send() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        setup_test(deps.as_mut(), 2, 8, curve_type);
+
+        // buy 20 EPOXY straight into a vault contract, which is told who they belong to
+        let info = mock_info(INVESTOR, &coins(2_000_000_000, DENOM));
+        let buy = ExecuteMsg::BuyAndSend {
+            contract: "vault".to_string(),
+            msg: Binary::from(b"deposit"),
+            min_tokens_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+        assert_eq!(get_balance(deps.as_ref(), "vault"), Uint128::new(2000));
         assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::zero());
-        assert_eq!(get_balance(deps.as_ref(), BUYER), Uint128::new(1000));
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(
+                Cw20ReceiveMsg {
+                    sender: INVESTOR.to_string(),
+                    amount: Uint128::new(2000),
+                    msg: Binary::from(b"deposit"),
+                }
+                .into_cosmos_msg("vault")
+                .unwrap()
+            )]
+        );
 
-        // second stake needs more to get next 1000 EPOXY
-        let info = mock_info(INVESTOR, &coins(1_500_000_000, DENOM));
+        // buy on behalf of someone else
+        let info = mock_info(INVESTOR, &coins(500_000_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: Some(BUYER.to_string()),
+            min_tokens_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+        assert_eq!(res.messages, vec![]);
+        assert!(res.attributes.contains(&attr("to", BUYER)));
+        assert!(!get_balance(deps.as_ref(), BUYER).is_zero());
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::zero());
+    }
+
+    #[test]
+    fn sell_proceeds_go_to_recipient() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        setup_test(deps.as_mut(), 2, 8, curve_type);
+        let refunded_to = |res: Response| match &res.messages[..] {
+            [SubMsg {
+                msg: CosmosMsg::Bank(BankMsg::Send { to_address, .. }),
+                ..
+            }] => to_address.clone(),
+            _ => panic!("expected a single refund, got {:?}", res.messages),
+        };
+
+        // buy 20 EPOXY and let the spender use half of them
+        let info = mock_info(INVESTOR, &coins(2_000_000_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
         execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+        let info = mock_info(INVESTOR, &[]);
+        let allow = ExecuteMsg::IncreaseAllowance {
+            spender: BUYER.into(),
+            amount: Uint128::new(1000),
+            expires: None,
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), allow).unwrap();
 
-        // ensure balances updated
-        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(1000));
-        assert_eq!(get_balance(deps.as_ref(), BUYER), Uint128::new(1000));
+        // the seller can have the refund sent elsewhere
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(500),
+            refund: Some(true),
+            recipient: Some(TREASURY.into()),
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, burn).unwrap();
+        assert_eq!(refunded_to(res), TREASURY);
 
-        // check curve info updated
-        let curve = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
-        assert_eq!(curve.reserve, Uint128::new(2_000_000_000));
-        assert_eq!(curve.supply, Uint128::new(2000));
-        assert_eq!(curve.spot_price, Decimal::percent(200));
+        // by default the spender is paid, and can pick a recipient too
+        let burn_from = |recipient: Option<&str>| ExecuteMsg::BurnFrom {
+            owner: INVESTOR.into(),
+            amount: Uint128::new(250),
+            refund: Some(true),
+            recipient: recipient.map(Into::into),
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let info = mock_info(BUYER, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), burn_from(None)).unwrap();
+        assert_eq!(refunded_to(res), BUYER);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            burn_from(Some(TREASURY)),
+        )
+        .unwrap();
+        assert_eq!(refunded_to(res), TREASURY);
 
-        // check token info updated
-        let token = query_token_info(deps.as_ref()).unwrap();
-        assert_eq!(token.decimals, 2);
-        assert_eq!(token.total_supply, Uint128::new(2000));
+        // once the owner is paid, the spender can't redirect the refund
+        let update = ExecuteMsg::UpdateConfig {
+            buy_fee_bps: None,
+            sell_fee_bps: None,
+            treasury: None,
+            fee_splits: None,
+            unrefunded_reserve: None,
+            burn_from_payout: Some(BurnFromPayout::Owner),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), update).unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            burn_from(Some(BUYER)),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::BurnFromRecipient {});
+        let res = execute(deps.as_mut(), mock_env(), info, burn_from(None)).unwrap();
+        assert_eq!(refunded_to(res), INVESTOR);
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(750));
     }
 
     #[test]
-    fn buy_exp_tokens() {
+    fn trades_emit_bonding_events() {
         let mut deps = mock_dependencies();
-        let curve_type = CurveType::Exponential {
-            slope: Uint128::new(55_000_000u128),
-            scale: 0,
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
         };
-        setup_test(deps.as_mut(), 2, 6, curve_type.clone());
+        setup_test(deps.as_mut(), 2, 8, curve_type.clone());
+        let curve = curve_type.to_curve_fn()(DecimalPlaces::new(2, 8));
+        let price_at = |supply: u128| curve.spot_price(Uint128::new(supply)).unwrap().to_string();
 
-        // succeeds with proper token (61.12 BTC - 6.112 BTC(Fee) = 55*10^6 satoshi)
-        let info = mock_info(INVESTOR, &coins(61_120_000, DENOM));
-        let buy = ExecuteMsg::Buy {};
-        execute(deps.as_mut(), mock_env(), info, buy.clone()).unwrap();
+        // buy 20 EPOXY for a friend
+        let info = mock_info(INVESTOR, &coins(2_000_000_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: Some(BUYER.to_string()),
+            min_tokens_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+        assert_eq!(
+            res.events,
+            vec![Event::new("bonding_trade").add_attributes(vec![
+                ("side", "buy"),
+                ("trader", INVESTOR),
+                ("recipient", BUYER),
+                ("reserve_in", "2000000000"),
+                ("tokens_out", "2000"),
+                ("fee", "0"),
+                ("spot_price_before", "0"),
+                ("spot_price_after", &price_at(2000)),
+                ("supply_after", "2000"),
+                ("reserve_after", "2000000000"),
+            ])]
+        );
 
-        // bob got 100 EPOXY (1.0)
-        let balance = get_balance(deps.as_ref(), INVESTOR);
-        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(100));
-        assert_eq!(get_balance(deps.as_ref(), BUYER), Uint128::zero());
+        // who sells half of them back, leaving the 15 BTC they were worth to the others
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(1000),
+            refund: None,
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(BUYER, &[]), burn).unwrap();
+        assert_eq!(
+            res.events,
+            vec![Event::new("bonding_trade").add_attributes(vec![
+                ("side", "sell"),
+                ("trader", BUYER),
+                ("recipient", MOCK_CONTRACT_ADDR),
+                ("reserve_out", "1500000000"),
+                ("tokens_in", "1000"),
+                ("fee", "0"),
+                ("spot_price_before", &price_at(2000)),
+                ("spot_price_after", &price_at(1000)),
+                ("supply_after", "1000"),
+                ("reserve_after", "500000000"),
+            ])]
+        );
 
-        // send them all to buyer
+        // with 10% fees the buy fee is part of what was paid in, the sell fee is already taken
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            buy_fee_bps: 1000,
+            sell_fee_bps: 1000,
+            ..default_instantiate(2, 8, curve_type)
+        };
+        setup_test_with(deps.as_mut(), msg);
+        let info = mock_info(INVESTOR, &coins(1_000_000_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+        assert_eq!(
+            res.events,
+            vec![Event::new("bonding_trade").add_attributes(vec![
+                ("side", "buy"),
+                ("trader", INVESTOR),
+                ("recipient", INVESTOR),
+                ("reserve_in", "1000000000"),
+                ("tokens_out", "1341"),
+                ("fee", "100000000"),
+                ("spot_price_before", "0"),
+                ("spot_price_after", &price_at(1341)),
+                ("supply_after", "1341"),
+                ("reserve_after", "900000000"),
+            ])]
+        );
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(1341),
+            refund: Some(true),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(INVESTOR, &[]), burn).unwrap();
+        assert_eq!(
+            res.events,
+            vec![Event::new("bonding_trade").add_attributes(vec![
+                ("side", "sell"),
+                ("trader", INVESTOR),
+                ("recipient", INVESTOR),
+                ("reserve_out", "810000000"),
+                ("tokens_in", "1341"),
+                ("fee", "90000000"),
+                ("spot_price_before", &price_at(1341)),
+                ("spot_price_after", "0"),
+                ("supply_after", "0"),
+                ("reserve_after", "0"),
+            ])]
+        );
+    }
+
+    #[test]
+    fn burn_without_refund_is_accounted() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        setup_test(deps.as_mut(), 2, 8, curve_type.clone());
+
+        // buy 20 EPOXY for 20 BTC and give half of them away
+        let info = mock_info(INVESTOR, &coins(2_000_000_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, buy).unwrap();
         let info = mock_info(INVESTOR, &[]);
-        let send = ExecuteMsg::Transfer {
+        let transfer = ExecuteMsg::Transfer {
             recipient: BUYER.into(),
+            amount: Uint128::new(1000),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), transfer).unwrap();
+
+        // burning the other half without refund donates the 15 BTC they were worth
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(1000),
+            refund: None,
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, burn).unwrap();
+        assert_eq!(res.messages, vec![]);
+        let curve = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
+        assert_eq!(curve.reserve, Uint128::new(500_000_000));
+        assert_eq!(curve.donated_reserve, Uint128::new(1_500_000_000));
+        assert_eq!(curve.supply, Uint128::new(1000));
+
+        // selling half of the remaining supply gets half of the donation on top of the curve
+        let info = mock_info(BUYER, &[]);
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(500),
+            refund: Some(true),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), burn).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: BUYER.into(),
+                amount: coins(375_000_000 + 750_000_000, DENOM),
+            })]
+        );
+        let curve = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
+        assert_eq!(curve.reserve, Uint128::new(125_000_000));
+        assert_eq!(curve.donated_reserve, Uint128::new(750_000_000));
+
+        // reconcile reports coins sent to the contract outside of the curve
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(900_000_000, DENOM));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(INVESTOR, &[]),
+            ExecuteMsg::Reconcile {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CREATOR, &[]),
+            ExecuteMsg::Reconcile {},
+        )
+        .unwrap();
+        let reconciled: ReconcileResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(
+            reconciled,
+            ReconcileResponse {
+                balance: Uint128::new(900_000_000),
+                tracked: Uint128::new(875_000_000),
+                surplus: Uint128::new(25_000_000),
+                shortfall: Uint128::zero(),
+            }
+        );
+
+        // or the released reserve can go to the treasury
+        let update = ExecuteMsg::UpdateConfig {
+            buy_fee_bps: None,
+            sell_fee_bps: None,
+            treasury: None,
+            fee_splits: None,
+            unrefunded_reserve: Some(UnrefundedReserve::Treasury),
+            burn_from_payout: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), update).unwrap();
+        let burn = ExecuteMsg::Burn {
             amount: Uint128::new(100),
+            refund: Some(false),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
         };
-        execute(deps.as_mut(), mock_env(), info, send).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info, burn).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: TREASURY.into(),
+                amount: coins(45_000_000 + 150_000_000, DENOM),
+            })]
+        );
+        let curve = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
+        assert_eq!(curve.reserve, Uint128::new(80_000_000));
+        assert_eq!(curve.donated_reserve, Uint128::new(600_000_000));
+    }
 
-        // ensure balances updated
-        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::zero());
-        assert_eq!(get_balance(deps.as_ref(), BUYER), Uint128::new(100));
+    #[test]
+    fn supply_cap_refunds_unused_payment() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        let msg = InstantiateMsg {
+            supply_cap: Some(Uint128::new(1000)),
+            ..default_instantiate(2, 8, curve_type.clone())
+        };
+        setup_test_with(deps.as_mut(), msg);
+        let token = query_token_info(deps.as_ref()).unwrap();
+        assert_eq!(token.total_supply, Uint128::zero());
 
-        // second stake needs more to get next 1000 EPOXY
-        // let info = mock_info(INVESTOR, &coins(155_563_500, DENOM));
-        // execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+        // 10 EPOXY cost 5 BTC, the other 3 BTC are refunded
+        let sim = query_simulate_buy(
+            deps.as_ref(),
+            curve_type.to_curve_fn(),
+            Uint128::new(800_000_000),
+        )
+        .unwrap();
+        assert_eq!(sim.reserve_amount, Uint128::new(500_000_000));
+        assert_eq!(sim.supply_amount, Uint128::new(1000));
 
-        // ensure balances updated
-        // assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(100));
-        // assert_eq!(get_balance(deps.as_ref(), BUYER), Uint128::new(100));
+        let info = mock_info(INVESTOR, &coins(800_000_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), buy.clone()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: INVESTOR.into(),
+                amount: coins(300_000_000, DENOM),
+            })]
+        );
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(1000));
+        let curve = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
+        assert_eq!(curve.reserve, Uint128::new(500_000_000));
 
-        // check curve info updated
-        // let curve = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
-        // assert_eq!(curve.reserve, Uint128::new(210_000_000));
-        // assert_eq!(curve.supply, Uint128::new(2_000_000));
-        // assert_eq!(curve.spot_price, Decimal::percent(28500));
+        let err = execute(deps.as_mut(), mock_env(), info, buy).unwrap_err();
+        assert_eq!(err, ContractError::SupplyCapReached {});
+    }
 
-        // check token info updated
-        // let token = query_token_info(deps.as_ref()).unwrap();
-        // assert_eq!(token.decimals, 6);
-        // assert_eq!(token.total_supply, Uint128::new(2_000_000));
+    #[test]
+    fn hatch_phase_sells_at_fixed_price() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        let msg = InstantiateMsg {
+            hatch: Some(HatchParams {
+                price: Decimal::one(),
+                raise_target: Some(Uint128::new(1_000_000_000)),
+                allowlist: vec![INVESTOR.to_string()],
+            }),
+            ..default_instantiate(2, 8, curve_type.clone())
+        };
+        setup_test_with(deps.as_mut(), msg);
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+
+        let info = mock_info(BUYER, &coins(500_000_000, DENOM));
+        let err = execute(deps.as_mut(), mock_env(), info, buy.clone()).unwrap_err();
+        assert_eq!(err, ContractError::NotAllowlisted {});
+
+        // 5 BTC buy 5 EPOXY at the hatch price, the curve only needs 1.25 BTC for them
+        let info = mock_info(INVESTOR, &coins(500_000_000, DENOM));
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), buy.clone()).unwrap();
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(500));
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: TREASURY.into(),
+                amount: coins(375_000_000, DENOM),
+            })]
+        );
+        let curve = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
+        assert_eq!(curve.reserve, Uint128::new(125_000_000));
+
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(100),
+            refund: Some(true),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info(INVESTOR, &[]), burn).unwrap_err();
+        assert_eq!(err, ContractError::HatchInProgress {});
+
+        // reaching the raise target opens the curve
+        execute(deps.as_mut(), mock_env(), info, buy.clone()).unwrap();
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(1000));
+        let phase = query_sale_phase(deps.as_ref()).unwrap();
+        assert_eq!(phase.phase, SalePhase::Open);
+        let curve = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
+        assert_eq!(curve.reserve, Uint128::new(500_000_000));
+
+        let info = mock_info(BUYER, &coins(1_500_000_000, DENOM));
+        execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+        assert_eq!(get_balance(deps.as_ref(), BUYER), Uint128::new(1000));
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CREATOR, &[]),
+            ExecuteMsg::OpenTrading {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::HatchOver {});
     }
 
     #[test]
-    fn bonding_fails_with_wrong_denom() {
+    fn hatch_price_must_cover_curve() {
         let mut deps = mock_dependencies();
         let curve_type = CurveType::Linear {
             slope: Uint128::new(1),
             scale: 1,
         };
-        setup_test(deps.as_mut(), 2, 8, curve_type);
-
-        // fails when no tokens sent
-        let info = mock_info(INVESTOR, &[]);
-        let buy = ExecuteMsg::Buy {};
-        let err = execute(deps.as_mut(), mock_env(), info, buy.clone()).unwrap_err();
-        assert_eq!(err, PaymentError::NoFunds {}.into());
+        let msg = InstantiateMsg {
+            hatch: Some(HatchParams {
+                price: Decimal::percent(10),
+                raise_target: None,
+                allowlist: vec![],
+            }),
+            ..default_instantiate(2, 8, curve_type)
+        };
+        setup_test_with(deps.as_mut(), msg);
 
-        // fails when wrong tokens sent                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                                              
-        let info = mock_info(INVESTOR, &coins(1234567, "wei"));
-        let err = execute(deps.as_mut(), mock_env(), info, buy.clone()).unwrap_err();
-        assert_eq!(err, PaymentError::MissingDenom(DENOM.into()).into());
+        let update = ExecuteMsg::UpdateHatchAllowlist {
+            add: vec![INVESTOR.to_string()],
+            remove: vec![],
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), update).unwrap();
+        assert!(
+            query_hatch_allowlisted(deps.as_ref(), INVESTOR.to_string())
+                .unwrap()
+                .allowlisted
+        );
 
-        // fails when too many tokens sent
-        let info = mock_info(INVESTOR, &[coin(3400022, DENOM), coin(1234567, "wei")]);
+        // 20 BTC would buy 200 EPOXY, but the curve needs 2000 BTC for them
+        let info = mock_info(INVESTOR, &coins(2_000_000_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
         let err = execute(deps.as_mut(), mock_env(), info, buy).unwrap_err();
-        assert_eq!(err, PaymentError::MultipleDenoms {}.into());
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("hatch price is below the curve"))
+        );
+
+        // the admin can open the curve without a raise target
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CREATOR, &[]),
+            ExecuteMsg::OpenTrading {},
+        )
+        .unwrap();
+        let phase = query_sale_phase(deps.as_ref()).unwrap();
+        assert_eq!(phase.phase, SalePhase::Open);
     }
 
     #[test]
-    fn burning_sends_reserve() {
+    fn augmented_curve_fills_funding_pool() {
         let mut deps = mock_dependencies();
         let curve_type = CurveType::Linear {
             slope: Uint128::new(1),
             scale: 1,
         };
-        setup_test(deps.as_mut(), 2, 8, curve_type.clone());
+        let funding = FundingParams {
+            reserve_ratio_bps: 8000,
+            exit_tribute_bps: 1000,
+            beneficiary: Addr::unchecked(BENEFICIARY),
+        };
+        let msg = InstantiateMsg {
+            funding: Some(FundingParams {
+                reserve_ratio_bps: 0,
+                ..funding.clone()
+            }),
+            ..default_instantiate(2, 8, curve_type.clone())
+        };
+        let err = instantiate(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidFunding {});
 
-        // succeeds with proper token (20 BTC = 20*10^8 satoshi)
-        let info = mock_info(INVESTOR, &coins(2_000_000_000, DENOM));
-        let buy = ExecuteMsg::Buy {};
-        execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+        let msg = InstantiateMsg {
+            funding: Some(funding),
+            ..default_instantiate(2, 8, curve_type.clone())
+        };
+        setup_test_with(deps.as_mut(), msg);
 
-        // bob got 2000 EPOXY (20.00)
+        // 20% of 25 BTC goes to the pool, the other 20 BTC buy 20 EPOXY
+        let sim = query_simulate_buy(
+            deps.as_ref(),
+            curve_type.to_curve_fn(),
+            Uint128::new(2_500_000_000),
+        )
+        .unwrap();
+        assert_eq!(sim.funding, Uint128::new(500_000_000));
+        assert_eq!(sim.supply_amount, Uint128::new(2000));
+        let info = mock_info(INVESTOR, &coins(2_500_000_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, buy).unwrap();
         assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(2000));
+        let curve = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
+        assert_eq!(curve.reserve, Uint128::new(2_000_000_000));
+        let pool = query_funding_pool(deps.as_ref()).unwrap();
+        assert_eq!(pool.balance, Uint128::new(500_000_000));
 
-        // cannot burn too much
-        let info = mock_info(INVESTOR, &[]);
-        let burn = ExecuteMsg::Burn {
-            amount: Uint128::new(3000),
-            refund: Some(true),
-        };
-        let err = execute(deps.as_mut(), mock_env(), info, burn).unwrap_err();
-        assert_eq!(
-            err,
-            ContractError::Base(cw20_base::ContractError::Std(StdError::overflow(
-                OverflowError::new(OverflowOperation::Sub, 2000, 3000)
-            )))
-        );
+        // selling back to 10 EPOXY releases 15 BTC, 10% of it stays in the pool
+        let sim = query_simulate_sell(deps.as_ref(), curve_type.to_curve_fn(), Uint128::new(1000))
+            .unwrap();
+        assert_eq!(sim.reserve_amount, Uint128::new(1_350_000_000));
+        assert_eq!(sim.funding, Uint128::new(150_000_000));
+        let reverse = query_reverse_simulate_sell(
+            deps.as_ref(),
+            curve_type.to_curve_fn(),
+            sim.reserve_amount,
+        )
+        .unwrap();
+        assert_eq!(reverse, sim);
 
-        // burn 1000 EPOXY to get back 15BTC (*10^8)
-        let info = mock_info(INVESTOR, &[]);
         let burn = ExecuteMsg::Burn {
             amount: Uint128::new(1000),
             refund: Some(true),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
         };
-        let res = execute(deps.as_mut(), mock_env(), info, burn).unwrap();
-
-        // balance is lower
-        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(1000));
-
-        // ensure we got our money back
-        assert_eq!(1, res.messages.len());
+        let res = execute(deps.as_mut(), mock_env(), mock_info(INVESTOR, &[]), burn).unwrap();
         assert_eq!(
-            &res.messages[0],
-            &SubMsg::new(BankMsg::Send {
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
                 to_address: INVESTOR.into(),
-                amount: coins(1_500_000_000, DENOM),
-            })
+                amount: coins(1_350_000_000, DENOM),
+            })]
         );
-
-        // check curve info updated
         let curve = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
         assert_eq!(curve.reserve, Uint128::new(500_000_000));
-        assert_eq!(curve.supply, Uint128::new(1000));
-        assert_eq!(curve.spot_price, Decimal::percent(100));
-
-        // check token info updated
-        let token = query_token_info(deps.as_ref()).unwrap();
-        assert_eq!(token.decimals, 2);
-        assert_eq!(token.total_supply, Uint128::new(1000));
-    }
-
-    #[test]
-    fn burning_exp_sends_reserve() {
-        let mut deps = mock_dependencies();
-        let curve_type = CurveType::Exponential {
-            slope: Uint128::new(55_000_000u128),
-            scale: 0,
-        };
-        setup_test(deps.as_mut(), 2, 6, curve_type.clone());
+        let pool = query_funding_pool(deps.as_ref()).unwrap();
+        assert_eq!(pool.balance, Uint128::new(650_000_000));
 
-        // succeeds with proper token (156 BTC = 156*10^6 satoshi)
-        let info = mock_info(INVESTOR, &coins(173_000_000, DENOM));
-        let buy = ExecuteMsg::Buy {};
-        execute(deps.as_mut(), mock_env(), info, buy).unwrap();
-
-        // bob got 200 EPOXY (2.0)
-        let balance = get_balance(deps.as_ref(), INVESTOR);
-        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(200));
-
-        // cannot burn too much
-        let info = mock_info(INVESTOR, &[]);
-        let burn = ExecuteMsg::Burn {
-            amount: Uint128::new(300),
-            refund: Some(true),
+        // only the beneficiary can withdraw, and only what is in the pool
+        let withdraw = ExecuteMsg::WithdrawFunding { amount: None };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CREATOR, &[]),
+            withdraw.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let too_much = ExecuteMsg::WithdrawFunding {
+            amount: Some(Uint128::new(700_000_000)),
         };
-        let err = execute(deps.as_mut(), mock_env(), info, burn).unwrap_err();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(BENEFICIARY, &[]),
+            too_much,
+        )
+        .unwrap_err();
         assert_eq!(
             err,
-            ContractError::Base(cw20_base::ContractError::Std(StdError::overflow(
-                OverflowError::new(OverflowOperation::Sub, 200, 300)
-            )))
+            ContractError::InsufficientFunding {
+                available: Uint128::new(650_000_000)
+            }
         );
-
-        // burn 100 EPOXY to get back 55 BTC (*10^6)
-        let info = mock_info(INVESTOR, &[]);
-        let burn = ExecuteMsg::Burn {
-            amount: Uint128::new(100),
-            refund: Some(true),
-        };
-        let res = execute(deps.as_mut(), mock_env(), info, burn).unwrap();
-
-        // balance is lower
-        let balance = get_balance(deps.as_ref(), INVESTOR);
-        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(100));
-
-        // ensure we got our money back
-        assert_eq!(1, res.messages.len());
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(BENEFICIARY, &[]),
+            withdraw,
+        )
+        .unwrap();
         assert_eq!(
-            &res.messages[0],
-            &SubMsg::new(BankMsg::Send {
-                to_address: INVESTOR.into(),
-                amount: coins(100_700_000, DENOM),
-            })
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: BENEFICIARY.into(),
+                amount: coins(650_000_000, DENOM),
+            })]
         );
-
-        // check curve info updated
-        let curve = query_curve_info(deps.as_ref(), curve_type.to_curve_fn()).unwrap();
-        assert_eq!(curve.reserve, Uint128::new(55_000_000));
-        assert_eq!(curve.supply, Uint128::new(100));
-
-        // check token info updated
-        let token = query_token_info(deps.as_ref()).unwrap();
-        assert_eq!(token.decimals, 2);
-        assert_eq!(token.total_supply, Uint128::new(100));
+        let pool = query_funding_pool(deps.as_ref()).unwrap();
+        assert_eq!(pool.balance, Uint128::zero());
     }
 
     #[test]
@@ -690,7 +3999,11 @@ mod tests {
 
         // spend 45_000 uatom for 30_000_000 EPOXY
         let info = mock_info(bob, &coins(45_000, DENOM));
-        let buy = ExecuteMsg::Buy {};
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
         execute(deps.as_mut(), mock_env(), info, buy).unwrap();
 
         // check balances
@@ -749,6 +4062,9 @@ mod tests {
             owner: bob.into(),
             amount: Uint128::new(3_300_000),
             refund: Some(true),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
         };
         let err = execute(deps.as_mut(), mock_env(), info, burn_from).unwrap_err();
         assert_eq!(
@@ -764,6 +4080,9 @@ mod tests {
             owner: bob.into(),
             amount: Uint128::new(1_000_000),
             refund: Some(true),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
         };
         let res = execute(deps.as_mut(), mock_env(), info, burn_from).unwrap();
 
@@ -781,4 +4100,412 @@ mod tests {
             })
         );
     }
-}
\ No newline at end of file
+    #[test]
+    fn admin_can_swap_curve() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Linear {
+            slope: Uint128::new(1),
+            scale: 1,
+        };
+        setup_test(deps.as_mut(), 2, 8, curve_type);
+
+        // buy 20 EPOXY for 20 STAKE
+        let info = mock_info(INVESTOR, &coins(2_000_000_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+        let update = |slope: u128, scale: u32, fit_reserve: bool| ExecuteMsg::UpdateCurve {
+            curve_type: CurveType::Linear {
+                slope: Uint128::new(slope),
+                scale,
+            },
+            fit_reserve,
+        };
+
+        // only the admin can swap the curve
+        let info = mock_info(INVESTOR, &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, update(1, 2, false)).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // a steeper curve would need more reserve than is bonded
+        let info = mock_info(CREATOR, &[]);
+        let err =
+            execute(deps.as_mut(), mock_env(), info.clone(), update(2, 1, false)).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InsufficientReserve {
+                required: Uint128::new(4_000_000_000),
+                available: Uint128::new(2_000_000_000),
+            }
+        );
+
+        // fitting scales the slope so the supply is priced at the reserve
+        execute(deps.as_mut(), mock_env(), info.clone(), update(25, 2, true)).unwrap();
+        let curve = CURVE_TYPE.load(&deps.storage).unwrap();
+        assert_eq!(
+            curve,
+            CurveType::Linear {
+                slope: Uint128::new(10),
+                scale: 2,
+            }
+        );
+        let state = CURVE_STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.supply, Uint128::new(2000));
+        assert_eq!(state.reserve, Uint128::new(2_000_000_000));
+        assert_eq!(state.donated_reserve, Uint128::zero());
+
+        // a flatter curve donates the surplus to the holders
+        let res = execute(deps.as_mut(), mock_env(), info, update(1, 2, false)).unwrap();
+        assert_eq!(res.attributes[2], attr("donated", "1800000000"));
+        let state = CURVE_STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.reserve, Uint128::new(200_000_000));
+        assert_eq!(state.donated_reserve, Uint128::new(1_800_000_000));
+
+        // so selling everything still pays out the whole reserve
+        let info = mock_info(INVESTOR, &[]);
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(2000),
+            refund: Some(true),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, burn).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: INVESTOR.into(),
+                amount: coins(2_000_000_000, DENOM),
+            })
+        );
+    }
+
+    #[test]
+    fn fitted_curves_keep_precision() {
+        let mut deps = mock_dependencies();
+        let linear = |slope: u128, scale: u32| CurveType::Linear {
+            slope: Uint128::new(slope),
+            scale,
+        };
+        // 20 EPOXY bonded with 38 BTC on a 0.19 slope
+        setup_test(deps.as_mut(), 2, 8, linear(19, 2));
+        let info = mock_info(INVESTOR, &coins(3_800_000_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+        let fit = |mut deps: DepsMut, curve_type: CurveType| {
+            let msg = ExecuteMsg::UpdateCurve {
+                curve_type,
+                fit_reserve: true,
+            };
+            let info = mock_info(CREATOR, &[]);
+            execute(deps.branch(), mock_env(), info, msg).unwrap();
+            let state = CURVE_STATE.load(deps.storage).unwrap();
+            (CURVE_TYPE.load(deps.storage).unwrap(), state)
+        };
+
+        // fitting a 0.1 slope needs 1.9 times the slope, more places than the curve had
+        let (curve, state) = fit(deps.as_mut(), linear(1, 1));
+        assert_eq!(curve, linear(19, 2));
+        assert_eq!(state.reserve, Uint128::new(3_800_000_000));
+        assert_eq!(state.donated_reserve, Uint128::zero());
+
+        // as does scaling a slope down
+        let (curve, state) = fit(deps.as_mut(), linear(2, 0));
+        assert_eq!(curve, linear(19, 2));
+        assert_eq!(state.donated_reserve, Uint128::zero());
+
+        // a slope with no short decimal is rounded down at 18 places, 38 / (20^1.5 / 1.5) here
+        let (curve, state) = fit(
+            deps.as_mut(),
+            CurveType::SquareRoot {
+                slope: Uint128::new(1),
+                scale: 0,
+            },
+        );
+        assert_eq!(
+            curve,
+            CurveType::SquareRoot {
+                slope: Uint128::new(637_279_373_694_255_126),
+                scale: 18,
+            }
+        );
+        assert_eq!(state.reserve, Uint128::new(3_800_000_000));
+        assert_eq!(state.donated_reserve, Uint128::zero());
+
+        // exponential slopes drop their fraction, so fitting one is refused
+        let msg = ExecuteMsg::UpdateCurve {
+            curve_type: CurveType::Exponential {
+                slope: Uint128::new(1),
+                scale: 0,
+            },
+            fit_reserve: true,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidCurve {
+                reason: "exponential curves can't be fitted to the reserve".to_string(),
+            }
+        );
+        let state = CURVE_STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.reserve, Uint128::new(3_800_000_000));
+        assert_eq!(state.donated_reserve, Uint128::zero());
+    }
+
+    #[test]
+    fn cw20_marketing_works() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Constant {
+            value: Uint128::new(15),
+            scale: 1,
+        };
+        let png = Binary::from(b"\x89PNG\r\n\x1a\nlogo".to_vec());
+        let msg = InstantiateMsg {
+            marketing: Some(InstantiateMarketingInfo {
+                project: Some("suitdrop".to_string()),
+                description: Some("Shirts on a curve".to_string()),
+                marketing: Some(CREATOR.to_string()),
+                logo: Some(Logo::Embedded(EmbeddedLogo::Png(png.clone()))),
+            }),
+            ..default_instantiate(9, 6, curve_type.clone())
+        };
+        setup_test_with(deps.as_mut(), msg);
+
+        assert_eq!(
+            query_marketing_info(deps.as_ref()).unwrap(),
+            MarketingInfoResponse {
+                project: Some("suitdrop".to_string()),
+                description: Some("Shirts on a curve".to_string()),
+                marketing: Some(Addr::unchecked(CREATOR)),
+                logo: Some(LogoInfo::Embedded),
+            }
+        );
+        let logo = query_download_logo(deps.as_ref()).unwrap();
+        assert_eq!(logo.mime_type, "image/png");
+        assert_eq!(logo.data, png);
+
+        // only the marketing address can change it
+        let update = ExecuteMsg::UpdateMarketing {
+            project: None,
+            description: Some("".to_string()),
+            marketing: None,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(INVESTOR, &[]),
+            update.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Base(cw20_base::ContractError::Unauthorized {})
+        );
+        execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), update).unwrap();
+        let upload = ExecuteMsg::UploadLogo(Logo::Url("https://suitdrop.xyz/logo.png".into()));
+        execute(deps.as_mut(), mock_env(), mock_info(CREATOR, &[]), upload).unwrap();
+        let info = query_marketing_info(deps.as_ref()).unwrap();
+        assert_eq!(info.description, None);
+        assert_eq!(
+            info.logo,
+            Some(LogoInfo::Url("https://suitdrop.xyz/logo.png".into()))
+        );
+
+        // an invalid logo fails the instantiation
+        let msg = InstantiateMsg {
+            marketing: Some(InstantiateMarketingInfo {
+                project: None,
+                description: None,
+                marketing: None,
+                logo: Some(Logo::Embedded(EmbeddedLogo::Png(b"not a png".into()))),
+            }),
+            ..default_instantiate(9, 6, curve_type)
+        };
+        let err = instantiate(
+            mock_dependencies().as_mut(),
+            mock_env(),
+            mock_info(CREATOR, &[]),
+            msg,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Base(cw20_base::ContractError::InvalidPngHeader {})
+        );
+    }
+
+    #[test]
+    fn cw20_enumerable_queries_work() {
+        let mut deps = mock_dependencies();
+        let curve_type = CurveType::Constant {
+            value: Uint128::new(15),
+            scale: 1,
+        };
+        setup_test(deps.as_mut(), 9, 6, curve_type);
+
+        // the contract mints, up to the supply cap
+        assert_eq!(
+            query_minter(deps.as_ref()).unwrap(),
+            Some(MinterResponse {
+                minter: MOCK_CONTRACT_ADDR.to_string(),
+                cap: None,
+            })
+        );
+
+        for buyer in ["alice", "bobby"] {
+            let buy = ExecuteMsg::Buy {
+                recipient: None,
+                min_tokens_out: None,
+                deadline: None,
+            };
+            let info = mock_info(buyer, &coins(45_000, DENOM));
+            execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+        }
+        for (owner, spender) in [("alice", "carl"), ("bobby", "carl"), ("bobby", "dave")] {
+            let allow = ExecuteMsg::IncreaseAllowance {
+                spender: spender.into(),
+                amount: Uint128::new(1000),
+                expires: None,
+            };
+            execute(deps.as_mut(), mock_env(), mock_info(owner, &[]), allow).unwrap();
+        }
+
+        let accounts = query_all_accounts(deps.as_ref(), None, None).unwrap();
+        assert_eq!(accounts.accounts, vec!["alice", "bobby"]);
+        let allowances = query_owner_allowances(deps.as_ref(), "bobby".into(), None, None).unwrap();
+        let spenders: Vec<_> = allowances.allowances.iter().map(|a| &a.spender).collect();
+        assert_eq!(spenders, vec!["carl", "dave"]);
+
+        // allowances by spender, paginated by owner
+        let allowances =
+            query_spender_allowances(deps.as_ref(), "carl".into(), None, Some(1)).unwrap();
+        assert_eq!(
+            allowances.allowances,
+            vec![SpenderAllowanceInfo {
+                owner: "alice".into(),
+                allowance: Uint128::new(1000),
+                expires: Expiration::Never {},
+            }]
+        );
+        let allowances =
+            query_spender_allowances(deps.as_ref(), "carl".into(), Some("alice".into()), None)
+                .unwrap();
+        let owners: Vec<_> = allowances.allowances.iter().map(|a| &a.owner).collect();
+        assert_eq!(owners, vec!["bobby"]);
+    }
+
+    #[test]
+    fn migrate_from_0_1() {
+        let mut deps = mock_dependencies();
+
+        // what 0.1 left in storage: 20 EPOXY bought for 20 BTC on a 0.1 slope, 10% buy fee
+        let storage = deps.as_mut().storage;
+        set_contract_version(storage, CONTRACT_NAME, "0.1.0").unwrap();
+        storage.set(
+            b"curve_state",
+            br#"{"reserve":"2000000000","supply":"2000","reserve_denom":"satoshi","decimals":{"supply":2,"reserve":8}}"#,
+        );
+        storage.set(b"config", br#"{"buy_fee":10,"treasury":"treasury"}"#);
+        storage.set(b"curve_type", br#"{"linear":{"slope":"1","scale":1}}"#);
+        let token_info = TokenInfo {
+            name: "Bonded".to_string(),
+            symbol: "EPOXY".to_string(),
+            decimals: 2,
+            total_supply: Uint128::new(2000),
+            mint: Some(MinterData {
+                minter: Addr::unchecked(MOCK_CONTRACT_ADDR),
+                cap: None,
+            }),
+        };
+        TOKEN_INFO.save(storage, &token_info).unwrap();
+        let investor = Addr::unchecked(INVESTOR);
+        BALANCES
+            .save(storage, &investor, &Uint128::new(2000))
+            .unwrap();
+        // and cw20-base 0.11 only stored allowances by owner
+        let allowance = AllowanceResponse {
+            allowance: Uint128::new(1000),
+            expires: Expiration::Never {},
+        };
+        let (owner, spender) = (Addr::unchecked("alice"), Addr::unchecked("carl"));
+        ALLOWANCES
+            .save(storage, (&owner, &spender), &allowance)
+            .unwrap();
+
+        // 0.1 had no admin, so one must be given
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg { admin: None }).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::MigrateAdminRequired {
+                version: "0.1.0".to_string()
+            }
+        );
+        // on chain the failed migration is reverted, version included
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.1.0").unwrap();
+        let msg = MigrateMsg {
+            admin: Some(CREATOR.to_string()),
+        };
+        migrate(deps.as_mut(), mock_env(), msg).unwrap();
+        let version = cw2::get_contract_version(&deps.storage).unwrap();
+        assert_eq!(version.version, CONTRACT_VERSION);
+        assert_eq!(ADMIN.load(&deps.storage).unwrap(), CREATOR);
+        let config = query_config(deps.as_ref()).unwrap();
+        assert_eq!(config.buy_fee_bps, 1000);
+        assert_eq!(config.sell_fee_bps, 0);
+        let allowances =
+            query_spender_allowances(deps.as_ref(), "carl".into(), None, None).unwrap();
+        assert_eq!(
+            allowances.allowances,
+            vec![SpenderAllowanceInfo {
+                owner: "alice".into(),
+                allowance: Uint128::new(1000),
+                expires: Expiration::Never {},
+            }]
+        );
+
+        // buys pay the converted fee, 9 BTC more bonded buys up to sqrt(580) EPOXY
+        let info = mock_info(INVESTOR, &coins(1_000_000_000, DENOM));
+        let buy = ExecuteMsg::Buy {
+            recipient: None,
+            min_tokens_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, buy).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: TREASURY.into(),
+                amount: coins(100_000_000, DENOM),
+            })
+        );
+        assert_eq!(get_balance(deps.as_ref(), INVESTOR), Uint128::new(2408));
+
+        // and selling everything pays out the whole reserve
+        let burn = ExecuteMsg::Burn {
+            amount: Uint128::new(2408),
+            refund: Some(true),
+            recipient: None,
+            min_reserve_out: None,
+            deadline: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(INVESTOR, &[]), burn).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: INVESTOR.into(),
+                amount: coins(2_900_000_000, DENOM),
+            })
+        );
+
+        // can't migrate back from a newer version
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "99.0.0").unwrap();
+        migrate(deps.as_mut(), mock_env(), MigrateMsg { admin: None }).unwrap_err();
+    }
+}
@@ -1,13 +1,10 @@
-use integer_cbrt::IntegerCubeRoot;
-use integer_sqrt::IntegerSquareRoot;
-use rust_decimal::prelude::{ToPrimitive, FromPrimitive};
-use rust_decimal::Decimal;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::ops::Add;
-use std::str::FromStr;
 
-use cosmwasm_std::{Decimal as StdDecimal, Uint128};
+use cosmwasm_std::{
+    CheckedFromRatioError, Decimal as StdDecimal, Decimal256, DivideByZeroError, StdError,
+    StdResult, Uint128, Uint256, Uint512,
+};
 
 /// This defines the curves we are using.
 ///
@@ -21,54 +18,81 @@ use cosmwasm_std::{Decimal as StdDecimal, Uint128};
 /// when you want to do more complex math than add and multiply `Uint128`, I decided to go the second
 /// route. That made the signatures quite complex and my final idea was to pass in `supply_decimal`
 /// and `reserve_decimal` in the curve constructors.
+///
+/// The math itself is done in `Decimal256`, which keeps 18 decimal places like the `Decimal`
+/// we report prices in, and has room for a u128 squared.
+///
+/// All methods return an error rather than panic when a value doesn't fit into the math types,
+/// so a trade that is too large fails instead of aborting the contract.
 pub trait Curve {
     /// Returns the spot price given the supply.
     /// `f(x)` from the README
-    fn spot_price(&self, supply: Uint128) -> StdDecimal;
+    fn spot_price(&self, supply: Uint128) -> StdResult<StdDecimal>;
 
     /// Returns the total price paid up to purchase supply tokens (integral)
     /// `F(x)` from the README
-    fn reserve(&self, supply: Uint128) -> Uint128;
+    fn reserve(&self, supply: Uint128) -> StdResult<Uint128>;
 
     /// Inverse of reserve. Returns how many tokens would be issued
     /// with a total paid amount of reserve.
     /// `F^-1(x)` from the README
-    fn supply(&self, reserve: Uint128) -> Uint128;
+    fn supply(&self, reserve: Uint128) -> StdResult<Uint128>;
 }
 
-/// decimal returns an object = num * 10 ^ -scale
+/// decimal returns an object = num * 10 ^ -scale, digits beyond 18 decimal places are dropped
 /// We use this function in contract.rs rather than call the crate constructor
 /// itself, in case we want to swap out the implementation, we can do it only in this file.
-pub fn decimal<T: Into<u128>>(num: T, scale: u32) -> Decimal {
-    Decimal::from_i128_with_scale(num.into() as i128, scale)
+pub fn decimal<T: Into<u128>>(num: T, scale: u32) -> Decimal256 {
+    // a u128 times 10^18 always fits into the Uint256 behind Decimal256
+    Decimal256::from_atomics(num.into(), scale).unwrap()
 }
 
-/// StdDecimal stores as a u128 with 18 decimal points of precision
-fn decimal_to_std(x: Decimal) -> StdDecimal {
-    // this seems straight-forward (if inefficient), converting via string representation
-    // TODO: execute errors better? Result?
-    StdDecimal::from_str(&x.to_string()).unwrap()
+/// Checked arithmetic for the curve math, overflows become errors instead of panics
+trait TryMath: Sized {
+    fn try_add(self, other: Self) -> StdResult<Self>;
+    fn try_sub(self, other: Self) -> StdResult<Self>;
+    fn try_mul(self, other: Self) -> StdResult<Self>;
+    fn try_div(self, other: Self) -> StdResult<Self>;
+}
+
+impl TryMath for Decimal256 {
+    fn try_add(self, other: Decimal256) -> StdResult<Decimal256> {
+        Ok(self.checked_add(other)?)
+    }
+
+    fn try_sub(self, other: Decimal256) -> StdResult<Decimal256> {
+        Ok(self.checked_sub(other)?)
+    }
 
-    // // maybe a better approach doing math, not sure about rounding
-    //
-    // // try to preserve decimal points, max 9
-    // let digits = min(x.scale(), 9);
-    // let multiplier = 10u128.pow(digits);
-    //
-    // // we multiply up before we round off to u128,
-    // // let StdDecimal do its best to keep these decimal places
-    // let nominator = (x * decimal(multiplier, 0)).to_u128().unwrap();
-    // StdDecimal::from_ratio(nominator, multiplier)
+    fn try_mul(self, other: Decimal256) -> StdResult<Decimal256> {
+        Ok(self.checked_mul(other)?)
+    }
+
+    fn try_div(self, other: Decimal256) -> StdResult<Decimal256> {
+        self.checked_div(other).map_err(|err| match err {
+            CheckedFromRatioError::DivideByZero => {
+                StdError::divide_by_zero(DivideByZeroError::new(self))
+            }
+            CheckedFromRatioError::Overflow => {
+                StdError::generic_err(format!("Cannot divide {} by {}", self, other))
+            }
+        })
+    }
+}
+
+/// StdDecimal has the same 18 decimal places, so this only checks the value fits into a u128
+fn decimal_to_std(x: Decimal256) -> StdResult<StdDecimal> {
+    Ok(StdDecimal::new(x.atomics().try_into()?))
 }
 
 /// spot price is always a constant value
 pub struct Constant {
-    pub value: Decimal,
+    pub value: Decimal256,
     pub normalize: DecimalPlaces,
 }
 
 impl Constant {
-    pub fn new(value: Decimal, normalize: DecimalPlaces) -> Self {
+    pub fn new(value: Decimal256, normalize: DecimalPlaces) -> Self {
         Self { value, normalize }
     }
 }
@@ -76,174 +100,481 @@ impl Constant {
 impl Curve for Constant {
     // we need to normalize value with the reserve decimal places
     // (eg 0.1 value would return 100_000 if reserve was uatom)
-    fn spot_price(&self, _supply: Uint128) -> StdDecimal {
+    fn spot_price(&self, _supply: Uint128) -> StdResult<StdDecimal> {
         // f(x) = self.value
         decimal_to_std(self.value)
     }
 
     /// Returns total number of reserve tokens needed to purchase a given number of supply tokens.
     /// Note that both need to be normalized.
-    fn reserve(&self, supply: Uint128) -> Uint128 {
+    fn reserve(&self, supply: Uint128) -> StdResult<Uint128> {
         // f(x) = supply * self.value
-        let reserve = self.normalize.from_supply(supply) * self.value;
+        let reserve = self.normalize.from_supply(supply).try_mul(self.value)?;
         self.normalize.to_reserve(reserve)
     }
 
-    fn supply(&self, reserve: Uint128) -> Uint128 {
+    fn supply(&self, reserve: Uint128) -> StdResult<Uint128> {
         // f(x) = reserve / self.value
-        let supply = self.normalize.from_reserve(reserve) / self.value;
+        let supply = self.normalize.from_reserve(reserve).try_div(self.value)?;
         self.normalize.to_supply(supply)
     }
 }
 
+/// Kept for existing instances: spot_price uses `supply + 1` while reserve uses `supply`,
+/// so it is not the derivative of reserve. Use `Power` with exponent 3/2 for new curves
 pub struct Exponential {
-    pub slope: Decimal,
+    pub slope: Decimal256,
     pub normalize: DecimalPlaces,
 }
 
 impl Exponential {
-    pub fn new(slope: Decimal, normalize: DecimalPlaces) -> Self {
+    pub fn new(slope: Decimal256, normalize: DecimalPlaces) -> Self {
         Self { slope, normalize }
     }
+
+    /// slope is read as a whole number of reserve units
+    fn slope(&self) -> Decimal256 {
+        // decimal() builds it from a u128, so the whole part fits
+        let whole = self.slope.atomics() / Uint256::from(10u32).pow(18);
+        let units = Uint128::try_from(whole).unwrap();
+        self.normalize.from_reserve(units)
+    }
 }
 
 impl Curve for Exponential {
     // factor is fixed as 1.5
-    fn spot_price(&self, supply: Uint128) -> StdDecimal {
+    fn spot_price(&self, supply: Uint128) -> StdResult<StdDecimal> {
         // f(x) = slope * ((supply + 1) ^ factor)
         // -> f(x) = slope * (supply + 1) * (supply + 1) ^ 0.5
         let supply_decimal = self.normalize.from_supply(supply);
-        let slope_decimal = self.normalize.from_reserve(self.slope.to_u128().unwrap().into());
-        let supply_wrap = supply_decimal + decimal(10u128.pow(self.normalize.supply), self.normalize.supply);
-        let price_decimal = slope_decimal * supply_wrap * square_root(supply_wrap);
+        let supply_wrap = supply_decimal.try_add(Decimal256::one())?;
+        let price_decimal = self
+            .slope()
+            .try_mul(supply_wrap)?
+            .try_mul(root(supply_wrap, 2))?;
         decimal_to_std(price_decimal)
     }
 
-    fn reserve(&self, supply: Uint128) -> Uint128 {
+    fn reserve(&self, supply: Uint128) -> StdResult<Uint128> {
         // reverse = slope * (supply ^ factor)
         // -> reverse = slope * supply * supply ^ 0.5
         let supply_decimal = self.normalize.from_supply(supply);
-        let slope_decimal = self.normalize.from_reserve(self.slope.to_u128().unwrap().into());
-        let reserve_decimal = slope_decimal * supply_decimal * square_root(supply_decimal);
+        let reserve_decimal = self
+            .slope()
+            .try_mul(supply_decimal)?
+            .try_mul(root(supply_decimal, 2))?;
         self.normalize.to_reserve(reserve_decimal)
     }
 
-    fn supply(&self, reserve: Uint128) -> Uint128 {
+    fn supply(&self, reserve: Uint128) -> StdResult<Uint128> {
         // supply = (reverse / slope) ^ 1/factor
         // -> supply = (reverse / slope) ^ 2/3
         let reserve_decimal = self.normalize.from_reserve(reserve);
-        let slope_decimal = self.normalize.from_reserve(self.slope.to_u128().unwrap().into());
-        let base = reserve_decimal / slope_decimal;
-        let squared = base * base;
-        let supply_decimal = cube_root(squared);
+        let base = reserve_decimal.try_div(self.slope())?;
+        let squared = base.try_mul(base)?;
+        let supply_decimal = root(squared, 3);
         self.normalize.to_supply(supply_decimal)
     }
 }
 
 /// spot_price is slope * supply
 pub struct Linear {
-    pub slope: Decimal,
+    pub slope: Decimal256,
     pub normalize: DecimalPlaces,
 }
 
 impl Linear {
-    pub fn new(slope: Decimal, normalize: DecimalPlaces) -> Self {
+    pub fn new(slope: Decimal256, normalize: DecimalPlaces) -> Self {
         Self { slope, normalize }
     }
 }
 
 impl Curve for Linear {
-    fn spot_price(&self, supply: Uint128) -> StdDecimal {
+    fn spot_price(&self, supply: Uint128) -> StdResult<StdDecimal> {
         // f(x) = supply * self.value
-        let out = self.normalize.from_supply(supply) * self.slope;
+        let out = self.normalize.from_supply(supply).try_mul(self.slope)?;
         decimal_to_std(out)
     }
 
-    fn reserve(&self, supply: Uint128) -> Uint128 {
+    fn reserve(&self, supply: Uint128) -> StdResult<Uint128> {
         // f(x) = self.slope * supply * supply / 2
         let normalized = self.normalize.from_supply(supply);
-        let square = normalized * normalized;
+        let square = normalized.try_mul(normalized)?;
         // Note: multiplying by 0.5 is much faster than dividing by 2
-        let reserve = square * self.slope * Decimal::new(5, 1);
+        let reserve = square
+            .try_mul(self.slope)?
+            .try_mul(Decimal256::percent(50))?;
         self.normalize.to_reserve(reserve)
     }
 
-    fn supply(&self, reserve: Uint128) -> Uint128 {
+    fn supply(&self, reserve: Uint128) -> StdResult<Uint128> {
         // f(x) = (2 * reserve / self.slope) ^ 0.5
-        // note: use addition here to optimize 2* operation
-        let square = self.normalize.from_reserve(reserve + reserve) / self.slope;
-        let supply = square_root(square);
+        let normalized = self.normalize.from_reserve(reserve);
+        let square = normalized.try_add(normalized)?.try_div(self.slope)?;
+        let supply = root(square, 2);
         self.normalize.to_supply(supply)
     }
 }
 
 /// spot_price is slope * (supply)^0.5
 pub struct SquareRoot {
-    pub slope: Decimal,
+    pub slope: Decimal256,
     pub normalize: DecimalPlaces,
 }
 
 impl SquareRoot {
-    pub fn new(slope: Decimal, normalize: DecimalPlaces) -> Self {
+    pub fn new(slope: Decimal256, normalize: DecimalPlaces) -> Self {
         Self { slope, normalize }
     }
 }
 
 impl Curve for SquareRoot {
-    fn spot_price(&self, supply: Uint128) -> StdDecimal {
+    fn spot_price(&self, supply: Uint128) -> StdResult<StdDecimal> {
         // f(x) = self.slope * supply^0.5
         let square = self.normalize.from_supply(supply);
-        let root = square_root(square);
-        decimal_to_std(root * self.slope)
+        decimal_to_std(root(square, 2).try_mul(self.slope)?)
     }
 
-    fn reserve(&self, supply: Uint128) -> Uint128 {
+    fn reserve(&self, supply: Uint128) -> StdResult<Uint128> {
         // f(x) = self.slope * supply * supply^0.5 / 1.5
         let normalized = self.normalize.from_supply(supply);
-        let root = square_root(normalized);
-        let reserve = self.slope * normalized * root / Decimal::new(15, 1);
+        let reserve = self
+            .slope
+            .try_mul(normalized)?
+            .try_mul(root(normalized, 2))?
+            .try_div(Decimal256::percent(150))?;
         self.normalize.to_reserve(reserve)
     }
 
-    fn supply(&self, reserve: Uint128) -> Uint128 {
+    fn supply(&self, reserve: Uint128) -> StdResult<Uint128> {
         // f(x) = (1.5 * reserve / self.slope) ^ (2/3)
-        let base = self.normalize.from_reserve(reserve) * Decimal::new(15, 1) / self.slope;
-        let squared = base * base;
-        let supply = cube_root(squared);
+        let base = self
+            .normalize
+            .from_reserve(reserve)
+            .try_mul(Decimal256::percent(150))?
+            .try_div(self.slope)?;
+        let squared = base.try_mul(base)?;
+        let supply = root(squared, 3);
         self.normalize.to_supply(supply)
     }
 }
 
-// we multiply by 10^18, turn to int, take square root, then divide by 10^9 as we convert back to decimal
-fn square_root(square: Decimal) -> Decimal {
-    // must be even
-    // TODO: this can overflow easily at 18... what is a good value?
-    const EXTRA_DIGITS: u32 = 12;
-    let multiplier = 10u128.saturating_pow(EXTRA_DIGITS);
+/// spot_price is slope * supply^(exponent_num / exponent_den)
+pub struct Power {
+    pub slope: Decimal256,
+    pub exponent_num: u32,
+    pub exponent_den: u32,
+    pub normalize: DecimalPlaces,
+}
+
+impl Power {
+    pub fn new(
+        slope: Decimal256,
+        exponent_num: u32,
+        exponent_den: u32,
+        normalize: DecimalPlaces,
+    ) -> Self {
+        Self {
+            slope,
+            exponent_num,
+            exponent_den,
+            normalize,
+        }
+    }
+}
+
+impl Curve for Power {
+    fn spot_price(&self, supply: Uint128) -> StdResult<StdDecimal> {
+        // f(x) = self.slope * supply^(n/d)
+        let normalized = self.normalize.from_supply(supply);
+        let power = pow_ratio(normalized, self.exponent_num, self.exponent_den)?;
+        decimal_to_std(self.slope.try_mul(power)?)
+    }
+
+    fn reserve(&self, supply: Uint128) -> StdResult<Uint128> {
+        // f(x) = self.slope * supply^((n+d)/d) * d/(n+d)
+        let normalized = self.normalize.from_supply(supply);
+        let sum = self.exponent_num + self.exponent_den;
+        let power = pow_ratio(normalized, sum, self.exponent_den)?;
+        let reserve = self
+            .slope
+            .try_mul(power)?
+            .try_mul(decimal(self.exponent_den, 0))?
+            .try_div(decimal(sum, 0))?;
+        self.normalize.to_reserve(reserve)
+    }
+
+    fn supply(&self, reserve: Uint128) -> StdResult<Uint128> {
+        // f(x) = (reserve * (n+d)/d / self.slope) ^ (d/(n+d))
+        let sum = self.exponent_num + self.exponent_den;
+        let base = self
+            .normalize
+            .from_reserve(reserve)
+            .try_mul(decimal(sum, 0))?
+            .try_div(decimal(self.exponent_den, 0))?
+            .try_div(self.slope)?;
+        let supply = pow_ratio(base, self.exponent_den, sum)?;
+        self.normalize.to_supply(supply)
+    }
+}
+
+/// One tier of a `Piecewise` curve, from `start` (in supply units) up to the next segment
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub start: Uint128,
+    pub price: Decimal256,
+    pub slope: Decimal256,
+}
+
+/// spot_price is `price + slope * (supply - start)` of the last segment starting at or below supply.
+/// Segments must be sorted by start and the first one start at 0
+pub struct Piecewise {
+    pub segments: Vec<Segment>,
+    pub normalize: DecimalPlaces,
+}
+
+impl Piecewise {
+    pub fn new(segments: Vec<Segment>, normalize: DecimalPlaces) -> Self {
+        Self {
+            segments,
+            normalize,
+        }
+    }
+
+    /// Yields (start, length, segment) in normalized supply, length is None for the last segment
+    fn tiers(&self) -> impl Iterator<Item = (Decimal256, Option<Decimal256>, &Segment)> + '_ {
+        self.segments.iter().enumerate().map(move |(i, segment)| {
+            let start = self.normalize.from_supply(segment.start);
+            // instantiate checks the segments are sorted
+            let length = self
+                .segments
+                .get(i + 1)
+                .map(|next| self.normalize.from_supply(next.start).saturating_sub(start));
+            (start, length, segment)
+        })
+    }
+}
+
+impl Curve for Piecewise {
+    fn spot_price(&self, supply: Uint128) -> StdResult<StdDecimal> {
+        let normalized = self.normalize.from_supply(supply);
+        let price = match self
+            .tiers()
+            .filter(|(start, _, _)| *start <= normalized)
+            .last()
+        {
+            Some((start, _, segment)) => segment
+                .price
+                .try_add(segment.slope.try_mul(normalized.try_sub(start)?)?)?,
+            None => Decimal256::zero(),
+        };
+        decimal_to_std(price)
+    }
 
-    // multiply by 10^18 and turn to u128
-    let extended = square * decimal(multiplier, 0);
-    let extended = extended.floor().to_u128().unwrap();
+    fn reserve(&self, supply: Uint128) -> StdResult<Uint128> {
+        // f(x) = sum of price * length + slope * length^2 / 2 over the segments below supply
+        let normalized = self.normalize.from_supply(supply);
+        let mut reserve = Decimal256::zero();
+        for (start, length, segment) in self.tiers() {
+            if normalized <= start {
+                break;
+            }
+            let covered = match length {
+                Some(length) => normalized.try_sub(start)?.min(length),
+                None => normalized.try_sub(start)?,
+            };
+            reserve = reserve.try_add(segment_cost(segment, covered)?)?;
+        }
+        self.normalize.to_reserve(reserve)
+    }
 
-    // take square root, and build a decimal again
-    let root = extended.integer_sqrt();
-    decimal(root, EXTRA_DIGITS / 2)
+    fn supply(&self, reserve: Uint128) -> StdResult<Uint128> {
+        // walk the segments until the remaining reserve doesn't fill one, then solve
+        // slope / 2 * t^2 + price * t = remaining for t
+        let mut remaining = self.normalize.from_reserve(reserve);
+        for (start, length, segment) in self.tiers() {
+            if let Some(length) = length {
+                let cost = segment_cost(segment, length)?;
+                if remaining >= cost {
+                    remaining = remaining.try_sub(cost)?;
+                    continue;
+                }
+            }
+            let covered = if segment.slope.is_zero() {
+                remaining.try_div(segment.price)?
+            } else {
+                let discriminant = segment.price.try_mul(segment.price)?.try_add(
+                    decimal(2u128, 0)
+                        .try_mul(segment.slope)?
+                        .try_mul(remaining)?,
+                )?;
+                root(discriminant, 2)
+                    .try_sub(segment.price)?
+                    .try_div(segment.slope)?
+            };
+            return self.normalize.to_supply(start.try_add(covered)?);
+        }
+        Ok(Uint128::zero())
+    }
 }
 
-// we multiply by 10^9, turn to int, take cube root, then divide by 10^3 as we convert back to decimal
-fn cube_root(cube: Decimal) -> Decimal {
-    // must be multiple of 3
-    // TODO: what is a good value?
-    const EXTRA_DIGITS: u32 = 9;
-    let multiplier = 10u128.saturating_pow(EXTRA_DIGITS);
+fn segment_cost(segment: &Segment, length: Decimal256) -> StdResult<Decimal256> {
+    let area = segment
+        .slope
+        .try_mul(length)?
+        .try_mul(length)?
+        .try_div(decimal(2u128, 0))?;
+    segment.price.try_mul(length)?.try_add(area)
+}
 
-    // multiply out and turn to u128
-    let extended = cube * decimal(multiplier, 0);
-    let extended = extended.floor().to_u128().unwrap();
+/// spot_price is `max_price / 2 * (1 + (supply - inflection) / sqrt(steepness + (supply - inflection)^2))`.
+/// Starts above 0, passes half of max_price at the inflection point and flattens towards max_price,
+/// a larger steepness stretches the curve out. inflection and steepness are in normalized supply
+pub struct Sigmoid {
+    pub max_price: Decimal256,
+    pub inflection: Decimal256,
+    pub steepness: Decimal256,
+    pub normalize: DecimalPlaces,
+}
 
-    // take cube root, and build a decimal again
-    let root = extended.integer_cbrt();
-    decimal(root, EXTRA_DIGITS / 3)
+impl Sigmoid {
+    pub fn new(
+        max_price: Decimal256,
+        inflection: Decimal256,
+        steepness: Decimal256,
+        normalize: DecimalPlaces,
+    ) -> Self {
+        Self {
+            max_price,
+            inflection,
+            steepness,
+            normalize,
+        }
+    }
+
+    /// sqrt(steepness + (x - inflection)^2)
+    fn distance(&self, x: Decimal256) -> StdResult<Decimal256> {
+        let offset = x.abs_diff(self.inflection);
+        Ok(root(self.steepness.try_add(offset.try_mul(offset)?)?, 2))
+    }
+}
+
+impl Curve for Sigmoid {
+    fn spot_price(&self, supply: Uint128) -> StdResult<StdDecimal> {
+        // f(x) = max_price / 2 * (1 + (x - m) / sqrt(s + (x - m)^2))
+        let normalized = self.normalize.from_supply(supply);
+        let half = self.max_price.try_div(decimal(2u128, 0))?;
+        let ratio = normalized
+            .abs_diff(self.inflection)
+            .try_div(self.distance(normalized)?)?;
+        let factor = if normalized >= self.inflection {
+            Decimal256::one().try_add(ratio)?
+        } else {
+            // the root is rounded down, don't let that push us below zero
+            Decimal256::one().saturating_sub(ratio)
+        };
+        decimal_to_std(half.try_mul(factor)?)
+    }
+
+    fn reserve(&self, supply: Uint128) -> StdResult<Uint128> {
+        // f(x) = max_price / 2 * (x + sqrt(s + (x - m)^2) - sqrt(s + m^2))
+        let normalized = self.normalize.from_supply(supply);
+        let half = self.max_price.try_div(decimal(2u128, 0))?;
+        // the roots are rounded separately, don't let that push us below zero
+        let reserve = half.try_mul(
+            normalized
+                .try_add(self.distance(normalized)?)?
+                .saturating_sub(self.distance(Decimal256::zero())?),
+        )?;
+        self.normalize.to_reserve(reserve)
+    }
+
+    fn supply(&self, reserve: Uint128) -> StdResult<Uint128> {
+        // with k = 2 * reserve / max_price + sqrt(s + m^2), solving x + sqrt(s + (x - m)^2) = k
+        // gives f(x) = (k^2 - s - m^2) / (2 * (k - m))
+        let normalized = self.normalize.from_reserve(reserve);
+        let two = decimal(2u128, 0);
+        let k = two
+            .try_mul(normalized)?
+            .try_div(self.max_price)?
+            .try_add(self.distance(Decimal256::zero())?)?;
+        let m = self.inflection;
+        // k^2 is at least s + m^2 up to rounding, and k is above m as s is positive
+        let supply = k
+            .try_mul(k)?
+            .saturating_sub(self.steepness.try_add(m.try_mul(m)?)?)
+            .try_div(two.try_mul(k.try_sub(m)?)?)?;
+        self.normalize.to_supply(supply)
+    }
+}
+
+/// x^(num/den), split into x^(num div den) * (x^(1/den))^(num mod den),
+/// so we only ever take roots of x itself and the intermediate values stay small
+fn pow_ratio(x: Decimal256, num: u32, den: u32) -> StdResult<Decimal256> {
+    let whole = pow(x, num / den)?;
+    let fraction = pow(root(x, den), num % den)?;
+    whole.try_mul(fraction)
+}
+
+fn pow(x: Decimal256, exp: u32) -> StdResult<Decimal256> {
+    (0..exp).try_fold(Decimal256::one(), |acc, _| acc.try_mul(x))
+}
+
+/// n-th root of x, rounded down to 18 decimal places. Only for huge x or n are the last
+/// digits dropped, to keep the scaled up radicand inside a Uint512.
+/// Natively it times about the same as the truncating roots it replaced, its wasm gas cost
+/// has not been measured
+fn root(x: Decimal256, n: u32) -> Decimal256 {
+    if n <= 1 || x.is_zero() {
+        return x;
+    }
+    // x^(1/n) * 10^18 = (atomics * 10^(18 * (n - 1)))^(1/n), and dropping `dropped` digits
+    // of the root takes n * dropped off the exponent
+    let atomics = Uint512::from(x.atomics());
+    let room = MAX_RADICAND_DIGITS - digits(atomics);
+    let exponent = 18 * (n - 1);
+    let dropped = exponent.saturating_sub(room).div_ceil(n);
+    let ten = Uint512::from(10u32);
+    let root = integer_root(atomics * ten.pow(exponent - n * dropped), n) * ten.pow(dropped);
+    // the root of x >= 1 is at most x, and below 1 otherwise, so it fits
+    Decimal256::new(root.try_into().unwrap())
+}
+
+/// Uint512 holds any number of up to 154 digits, we keep one spare
+const MAX_RADICAND_DIGITS: u32 = 153;
+
+/// Number of decimal digits in x
+fn digits(x: Uint512) -> u32 {
+    let ten = Uint512::from(10u32);
+    let mut digits = 1;
+    let mut bound = ten;
+    while bound <= x {
+        digits += 1;
+        bound *= ten;
+    }
+    digits
+}
+
+/// Largest r with r^n <= x. Newton's method started above the root only steps down,
+/// so it stops exactly at the rounded down root
+fn integer_root(x: Uint512, n: u32) -> Uint512 {
+    if x.is_zero() || n <= 1 {
+        return x;
+    }
+    let wide_n = Uint512::from(n);
+    // 10^ceil(digits / n) is above the root
+    let mut root = Uint512::from(10u32).pow(digits(x).div_ceil(n));
+    loop {
+        // a power that doesn't fit a Uint512 is larger than x, so the quotient is 0
+        let quotient = root
+            .checked_pow(n - 1)
+            .map_or(Uint512::zero(), |power| x / power);
+        let next = (root * (wide_n - Uint512::one()) + quotient) / wide_n;
+        if next >= root {
+            return root;
+        }
+        root = next;
+    }
 }
 
 /// DecimalPlaces should be passed into curve constructors
@@ -263,32 +594,36 @@ impl DecimalPlaces {
         }
     }
 
-    pub fn to_reserve(self, reserve: Decimal) -> Uint128 {
-        let factor = decimal(10u128.pow(self.reserve), 0);
-        let out = reserve * factor;
-        // TODO: execute overflow better? Result?
-        out.floor().to_u128().unwrap().into()
+    pub fn to_reserve(self, reserve: Decimal256) -> StdResult<Uint128> {
+        to_units(reserve, self.reserve)
     }
 
-    pub fn to_supply(self, supply: Decimal) -> Uint128 {
-        let factor = decimal(10u128.pow(self.supply), 0);
-        let out = supply * factor;
-        // TODO: execute overflow better? Result?
-        out.floor().to_u128().unwrap().into()
+    pub fn to_supply(self, supply: Decimal256) -> StdResult<Uint128> {
+        to_units(supply, self.supply)
     }
 
-    pub fn from_supply(&self, supply: Uint128) -> Decimal {
+    pub fn from_supply(&self, supply: Uint128) -> Decimal256 {
         decimal(supply, self.supply)
     }
 
-    pub fn from_reserve(&self, reserve: Uint128) -> Decimal {
+    pub fn from_reserve(&self, reserve: Uint128) -> Decimal256 {
         decimal(reserve, self.reserve)
     }
 }
 
+/// Scales a normalized amount up by 10^places and rounds it down to whole units
+fn to_units(amount: Decimal256, places: u32) -> StdResult<Uint128> {
+    // atomics have 18 decimal places, so that is atomics * 10^places / 10^18
+    let ten = Uint512::from(10u32);
+    let factor = ten.checked_pow(places)?;
+    let units = Uint512::from(amount.atomics()).checked_mul(factor)? / ten.pow(18);
+    Ok(units.try_into()?)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::str::FromStr;
     // TODO: test DecimalPlaces return proper decimals
 
     #[test]
@@ -301,15 +636,15 @@ mod tests {
         // spot price is always 1.5 ATOM
         assert_eq!(
             StdDecimal::percent(150),
-            curve.spot_price(Uint128::new(123))
+            curve.spot_price(Uint128::new(123)).unwrap()
         );
 
         // if we have 30 STEP, we should have 45 ATOM
-        let reserve = curve.reserve(Uint128::new(30_000_000_000));
+        let reserve = curve.reserve(Uint128::new(30_000_000_000)).unwrap();
         assert_eq!(Uint128::new(45_000_000), reserve);
 
         // if we have 36 ATOM, we should have 24 STEP
-        let supply = curve.supply(Uint128::new(36_000_000));
+        let supply = curve.supply(Uint128::new(36_000_000)).unwrap();
         assert_eq!(Uint128::new(24_000_000_000), supply);
     }
 
@@ -319,22 +654,17 @@ mod tests {
         let normalize = DecimalPlaces::new(0, 6);
         let curve = Exponential::new(decimal(55_000_000u128, 0), normalize);
 
-        // do some sanity checks....
-        let va1 = curve.spot_price(Uint128::new(0));
-        let va2 = curve.spot_price(Uint128::new(1));
-        let va3 = curve.spot_price(Uint128::new(2));
-        let va4 = curve.spot_price(Uint128::new(99));
-
         // if we have 1 STEP, we should have 55 OSMO
-        let reserve = curve.reserve(Uint128::new(1));
+        let reserve = curve.reserve(Uint128::new(1)).unwrap();
         assert_eq!(Uint128::new(55_000_000), reserve);
 
-        // if we have 2 STEP, we should have 155 OSMO
-        let reserve = curve.reserve(Uint128::new(2));
-        assert_eq!(Uint128::new(155_563_430), reserve);
+        // if we have 2 STEP, we should have 55 * 2 * sqrt(2) = 155.563491... OSMO
+        // (155.563430 while the square root was cut at 6 decimal places)
+        let reserve = curve.reserve(Uint128::new(2)).unwrap();
+        assert_eq!(Uint128::new(155_563_491), reserve);
 
         // if we have 155 OSMO, we should have 2 STEP
-        let supply = curve.supply(Uint128::new(155_563_500));
+        let supply = curve.supply(Uint128::new(155_563_500)).unwrap();
         assert_eq!(Uint128::new(2), supply);
     }
 
@@ -349,33 +679,33 @@ mod tests {
         // spot price is 0.1 with 1 USDT supply
         assert_eq!(
             StdDecimal::permille(100),
-            curve.spot_price(Uint128::new(100))
+            curve.spot_price(Uint128::new(100)).unwrap()
         );
         // spot price is 1.7 with 17 USDT supply
         assert_eq!(
             StdDecimal::permille(1700),
-            curve.spot_price(Uint128::new(1700))
+            curve.spot_price(Uint128::new(1700)).unwrap()
         );
         // spot price is 0.212 with 2.12 USDT supply
         assert_eq!(
             StdDecimal::permille(212),
-            curve.spot_price(Uint128::new(212))
+            curve.spot_price(Uint128::new(212)).unwrap()
         );
 
         // if we have 10 USDT, we should have 5 BTC
-        let reserve = curve.reserve(Uint128::new(1000));
+        let reserve = curve.reserve(Uint128::new(1000)).unwrap();
         assert_eq!(Uint128::new(500_000_000), reserve);
         // if we have 20 USDT, we should have 20 BTC
-        let reserve = curve.reserve(Uint128::new(2000));
+        let reserve = curve.reserve(Uint128::new(2000)).unwrap();
         assert_eq!(Uint128::new(2_000_000_000), reserve);
 
         // if we have 1.25 BTC, we should have 5 USDT
-        let supply = curve.supply(Uint128::new(125_000_000));
+        let supply = curve.supply(Uint128::new(125_000_000)).unwrap();
         assert_eq!(Uint128::new(500), supply);
         // test square root rounding
         // TODO: test when supply has many more decimal places than reserve
         // if we have 1.11 BTC, we should have 4.7116875957... USDT
-        let supply = curve.supply(Uint128::new(111_000_000));
+        let supply = curve.supply(Uint128::new(111_000_000)).unwrap();
         assert_eq!(Uint128::new(471), supply);
     }
 
@@ -390,38 +720,325 @@ mod tests {
         // spot price is 0.35 with 1 TREE supply
         assert_eq!(
             StdDecimal::percent(35),
-            curve.spot_price(Uint128::new(1_000_000))
+            curve.spot_price(Uint128::new(1_000_000)).unwrap()
         );
         // spot price is 3.5 with 100 TREE supply
         assert_eq!(
             StdDecimal::percent(350),
-            curve.spot_price(Uint128::new(100_000_000))
+            curve.spot_price(Uint128::new(100_000_000)).unwrap()
         );
-        // spot price should be 23.478713763747788 with 4500 TREE supply (test rounding and reporting here)
-        // rounds off around 8-9 sig figs (note diff for last points)
+        // spot price should be 23.4787137637477918122... with 4500 TREE supply (test rounding and
+        // reporting here). The root is exact to 18 decimal places, cutting it at 6 gave 23.47871365
         assert_eq!(
-            StdDecimal::from_ratio(2347871365u128, 100_000_000u128),
-            curve.spot_price(Uint128::new(4_500_000_000))
+            StdDecimal::from_str("23.478713763747791812").unwrap(),
+            curve.spot_price(Uint128::new(4_500_000_000)).unwrap()
         );
 
         // if we have 1 TREE, we should have 0.2333333333333 CHF
-        let reserve = curve.reserve(Uint128::new(1_000_000));
+        let reserve = curve.reserve(Uint128::new(1_000_000)).unwrap();
         assert_eq!(Uint128::new(23), reserve);
         // if we have 100 TREE, we should have 233.333333333 CHF
-        let reserve = curve.reserve(Uint128::new(100_000_000));
+        let reserve = curve.reserve(Uint128::new(100_000_000)).unwrap();
         assert_eq!(Uint128::new(23_333), reserve);
         // test rounding
         // if we have 235 TREE, we should have 840.5790828021146 CHF
-        let reserve = curve.reserve(Uint128::new(235_000_000));
+        let reserve = curve.reserve(Uint128::new(235_000_000)).unwrap();
         assert_eq!(Uint128::new(84_057), reserve); // round down
 
-        // // if we have 0.23 CHF, we should have 0.990453 TREE (round down)
-        let supply = curve.supply(Uint128::new(23));
-        assert_eq!(Uint128::new(990_000), supply);
+        // the cube root used to be cut at 3 decimal places, which gave 0.990000 and 235.000000
+        // TREE below
+        // if we have 0.23 CHF, we should have 0.990453 TREE (round down)
+        let supply = curve.supply(Uint128::new(23)).unwrap();
+        assert_eq!(Uint128::new(990_453), supply);
         // if we have 840.58 CHF, we should have 235.000170 TREE (round down)
-        let supply = curve.supply(Uint128::new(84058));
-        assert_eq!(Uint128::new(235_000_000), supply);
+        let supply = curve.supply(Uint128::new(84058)).unwrap();
+        assert_eq!(Uint128::new(235_000_170), supply);
     }
 
-    // Idea: generic test that curve.supply(curve.reserve(supply)) == supply (or within some small rounding margin)
+    #[test]
+    fn power_curve() {
+        // supply is ustake (6), reserve is uatom (6)
+        let normalize = DecimalPlaces::new(6, 6);
+        // quadratic, slope is 0.01 (eg hits 1.0 after 10 STAKE)
+        let curve = Power::new(decimal(1u128, 2), 2, 1, normalize);
+
+        // spot price is 1.0 with 10 STAKE supply
+        assert_eq!(
+            StdDecimal::one(),
+            curve.spot_price(Uint128::new(10_000_000)).unwrap()
+        );
+        // if we have 30 STAKE, we should have 90 ATOM
+        let reserve = curve.reserve(Uint128::new(30_000_000)).unwrap();
+        assert_eq!(Uint128::new(90_000_000), reserve);
+        // if we have 90 ATOM, we should have 30 STAKE
+        let supply = curve.supply(Uint128::new(90_000_000)).unwrap();
+        assert_eq!(Uint128::new(30_000_000), supply);
+
+        // supply and reserve have 2 decimals, exponent is 3/2
+        let normalize = DecimalPlaces::new(2, 2);
+        let curve = Power::new(decimal(1u128, 0), 3, 2, normalize);
+
+        // spot price is 8 with 4 tokens supply
+        assert_eq!(
+            StdDecimal::percent(800),
+            curve.spot_price(Uint128::new(400)).unwrap()
+        );
+        // if we have 4 tokens, we should have 4^2.5 * 2/5 = 12.8 reserve
+        let reserve = curve.reserve(Uint128::new(400)).unwrap();
+        assert_eq!(Uint128::new(1280), reserve);
+        // and back again
+        let supply = curve.supply(Uint128::new(1280)).unwrap();
+        assert_eq!(Uint128::new(400), supply);
+        // test rounding, 2^1.5 = 2.828427...
+        assert_eq!(
+            StdDecimal::from_str("2.828427124746190096").unwrap(),
+            curve.spot_price(Uint128::new(200)).unwrap()
+        );
+    }
+
+    #[test]
+    fn integer_root_rounds_down() {
+        let root = |x: u128, n| integer_root(Uint512::from(x), n);
+        assert_eq!(Uint512::zero(), root(0, 3));
+        assert_eq!(Uint512::from(2u32), root(26, 3));
+        assert_eq!(Uint512::from(3u32), root(27, 3));
+        assert_eq!(Uint512::one(), root(u128::MAX, 200));
+        assert_eq!(Uint512::from(u128::MAX), root(u128::MAX, 1));
+        // 2^128 - 1 is just below (2^64)^2
+        assert_eq!(Uint512::from(u64::MAX), root(u128::MAX, 2));
+        // and the largest radicand root() builds
+        let radicand = Uint512::from(10u32).pow(153) - Uint512::one();
+        assert_eq!(
+            Uint512::from(10u32).pow(51) - Uint512::one(),
+            integer_root(radicand, 3)
+        );
+    }
+
+    #[test]
+    fn piecewise_curve() {
+        // supply is ustake (6), reserve is uatom (6)
+        let normalize = DecimalPlaces::new(6, 6);
+        // 1 ATOM for the first 10 STAKE, then rising by 0.1 for 10 STAKE, then flat at 3 ATOM
+        let segments = vec![
+            Segment {
+                start: Uint128::zero(),
+                price: decimal(1u128, 0),
+                slope: Decimal256::zero(),
+            },
+            Segment {
+                start: Uint128::new(10_000_000),
+                price: decimal(1u128, 0),
+                slope: decimal(1u128, 1),
+            },
+            Segment {
+                start: Uint128::new(20_000_000),
+                price: decimal(3u128, 0),
+                slope: Decimal256::zero(),
+            },
+        ];
+        let curve = Piecewise::new(segments, normalize);
+
+        // spot price is 1 with 5 STAKE supply
+        assert_eq!(
+            StdDecimal::one(),
+            curve.spot_price(Uint128::new(5_000_000)).unwrap()
+        );
+        // spot price is 1.5 with 15 STAKE supply
+        assert_eq!(
+            StdDecimal::percent(150),
+            curve.spot_price(Uint128::new(15_000_000)).unwrap()
+        );
+        // price jumps to 3 right at the last breakpoint
+        assert_eq!(
+            StdDecimal::percent(300),
+            curve.spot_price(Uint128::new(20_000_000)).unwrap()
+        );
+
+        // if we have 10 STAKE, we should have 10 ATOM
+        let reserve = curve.reserve(Uint128::new(10_000_000)).unwrap();
+        assert_eq!(Uint128::new(10_000_000), reserve);
+        // if we have 20 STAKE, we should have 10 + 10 + 5 = 25 ATOM
+        let reserve = curve.reserve(Uint128::new(20_000_000)).unwrap();
+        assert_eq!(Uint128::new(25_000_000), reserve);
+        // if we have 30 STAKE, we should have 25 + 30 = 55 ATOM
+        let reserve = curve.reserve(Uint128::new(30_000_000)).unwrap();
+        assert_eq!(Uint128::new(55_000_000), reserve);
+
+        // if we have 25 ATOM, we should have 20 STAKE
+        let supply = curve.supply(Uint128::new(25_000_000)).unwrap();
+        assert_eq!(Uint128::new(20_000_000), supply);
+        // if we have 40 ATOM, we should have 20 + 15 / 3 = 25 STAKE
+        let supply = curve.supply(Uint128::new(40_000_000)).unwrap();
+        assert_eq!(Uint128::new(25_000_000), supply);
+        // test square root rounding
+        // if we have 17.5 ATOM, we should have 10 + (sqrt(2.5) - 1) / 0.1 = 15.8113883008... STAKE
+        let supply = curve.supply(Uint128::new(17_500_000)).unwrap();
+        assert_eq!(Uint128::new(15_811_388), supply);
+    }
+
+    #[test]
+    fn sigmoid_curve() {
+        // supply is ustake (6), reserve is uatom (6)
+        let normalize = DecimalPlaces::new(6, 6);
+        // flattens at 2 ATOM, half way there at 10 STAKE
+        let curve = Sigmoid::new(
+            decimal(2u128, 0),
+            decimal(10u128, 0),
+            decimal(100u128, 0),
+            normalize,
+        );
+
+        // spot price is 1 at the inflection point
+        assert_eq!(
+            StdDecimal::one(),
+            curve.spot_price(Uint128::new(10_000_000)).unwrap()
+        );
+        // spot price is 1 - 10 / sqrt(200) = 0.2928932188134524755... with no supply
+        // (sqrt is rounded down, which rounds the result up)
+        assert_eq!(
+            StdDecimal::from_str("0.292893218813452476").unwrap(),
+            curve.spot_price(Uint128::zero()).unwrap()
+        );
+        // spot price is 1 + 990 / sqrt(980200) = 1.99994898870096408... with 1000 STAKE supply
+        assert_eq!(
+            StdDecimal::from_str("1.999948988700964085").unwrap(),
+            curve.spot_price(Uint128::new(1_000_000_000)).unwrap()
+        );
+
+        // no supply, no reserve
+        let reserve = curve.reserve(Uint128::zero()).unwrap();
+        assert_eq!(Uint128::zero(), reserve);
+        // if we have 10 STAKE, we should have 20 - sqrt(200) = 5.857864376... ATOM
+        let reserve = curve.reserve(Uint128::new(10_000_000)).unwrap();
+        assert_eq!(Uint128::new(5_857_864), reserve);
+
+        // if we have 5.857865 ATOM, we should have 10.0000005... STAKE (round down)
+        let supply = curve.supply(Uint128::new(5_857_865)).unwrap();
+        assert_eq!(Uint128::new(10_000_000), supply);
+        // if we have 5.857864 ATOM, we should have 9.9999996... STAKE (round down)
+        let supply = curve.supply(Uint128::new(5_857_864)).unwrap();
+        assert_eq!(Uint128::new(9_999_999), supply);
+    }
+
+    #[test]
+    fn extreme_values_error_instead_of_panicking() {
+        let normalize = DecimalPlaces::new(6, 6);
+        let segments = vec![
+            Segment {
+                start: Uint128::zero(),
+                price: decimal(1u128, 0),
+                slope: decimal(1u128, 1),
+            },
+            Segment {
+                start: Uint128::new(1_000_000),
+                price: decimal(2u128, 0),
+                slope: Decimal256::zero(),
+            },
+        ];
+        let curves: Vec<(&str, Box<dyn Curve>)> = vec![
+            (
+                "constant",
+                Box::new(Constant::new(decimal(15u128, 1), normalize)),
+            ),
+            (
+                "exponential",
+                Box::new(Exponential::new(decimal(1u128, 0), normalize)),
+            ),
+            (
+                "linear",
+                Box::new(Linear::new(decimal(1u128, 1), normalize)),
+            ),
+            (
+                "square root",
+                Box::new(SquareRoot::new(decimal(35u128, 2), normalize)),
+            ),
+            (
+                "power",
+                Box::new(Power::new(decimal(1u128, 2), 3, 2, normalize)),
+            ),
+            ("piecewise", Box::new(Piecewise::new(segments, normalize))),
+            (
+                "sigmoid",
+                Box::new(Sigmoid::new(
+                    decimal(2u128, 0),
+                    decimal(10u128, 0),
+                    decimal(100u128, 0),
+                    normalize,
+                )),
+            ),
+        ];
+
+        for (name, curve) in curves {
+            // nothing panics at the top of the range, whether a result fits depends on the curve
+            for amount in [
+                Uint128::MAX,
+                Uint128::MAX - Uint128::one(),
+                Uint128::new(1 << 96),
+            ] {
+                let _ = curve.spot_price(amount);
+                let _ = curve.reserve(amount);
+                let _ = curve.supply(amount);
+            }
+            // and the small end still works
+            assert_eq!(
+                Uint128::zero(),
+                curve.reserve(Uint128::zero()).unwrap(),
+                "{}",
+                name
+            );
+            assert_eq!(
+                Uint128::zero(),
+                curve.supply(Uint128::zero()).unwrap(),
+                "{}",
+                name
+            );
+            curve.spot_price(Uint128::zero()).unwrap();
+            curve.spot_price(Uint128::new(1)).unwrap();
+        }
+
+        let curve = Linear::new(decimal(1u128, 1), normalize);
+        // a u128 supply squared doesn't fit, nor does its price fit a Decimal
+        assert!(curve.reserve(Uint128::MAX).is_err());
+        assert!(curve.spot_price(Uint128::MAX).is_err());
+        // but whatever a u128 reserve buys can be priced
+        let supply = curve.supply(Uint128::MAX).unwrap();
+        assert!(curve.reserve(supply).unwrap() <= Uint128::MAX);
+
+        // more decimal places than a Uint512 can scale up to
+        let curve = Linear::new(decimal(1u128, 1), DecimalPlaces::new(255, 255));
+        assert!(curve.reserve(Uint128::new(1)).is_err());
+        assert!(curve.supply(Uint128::new(1)).is_err());
+    }
+
+    mod power_proptest {
+        use super::*;
+        use proptest::prelude::*;
+
+        proptest! {
+            #[test]
+            fn supply_of_reserve_round_trips(
+                whole in 1u128..1_000,
+                fraction in 0u128..1_000_000_000,
+                supply_places in 0u32..=9,
+                reserve_places in 0u32..=9,
+                slope in 1u128..=100,
+                exponent_num in 0u32..=4,
+                exponent_den in 1u32..=4,
+            ) {
+                let normalize = DecimalPlaces::new(supply_places as u8, reserve_places as u8);
+                let curve = Power::new(decimal(slope, 2), exponent_num, exponent_den, normalize);
+                let supply = whole * 10u128.pow(supply_places) + fraction % 10u128.pow(supply_places);
+
+                let reserve = curve.reserve(Uint128::new(supply)).unwrap();
+                // too few reserve units to say anything about the supply
+                prop_assume!(reserve.u128() >= 1_000);
+                let back = curve.supply(reserve).unwrap().u128();
+
+                // reserve rounds down, so we can never get back more than we started with
+                prop_assert!(back <= supply, "{} > {}", back, supply);
+                // but only lose what rounding the reserve to whole units costs (< 0.1%)
+                prop_assert!((supply - back) * 1_000 <= supply + 1_000, "{} far from {}", back, supply);
+            }
+        }
+    }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
 use cw_utils::PaymentError;
 use thiserror::Error;
 
@@ -15,4 +15,99 @@ pub enum ContractError {
 
     #[error("Unauthorized")]
     Unauthorized {},
+
+    #[error("Migrating from {version} needs an admin")]
+    MigrateAdminRequired { version: String },
+
+    #[error("Name is not in the expected format (3-50 UTF-8 bytes)")]
+    InvalidName {},
+
+    #[error("Ticker symbol is not in expected format [a-zA-Z\\-]{{3,12}}")]
+    InvalidSymbol {},
+
+    #[error("Decimals of {decimals} are more than 18")]
+    InvalidDecimals { decimals: u8 },
+
+    #[error("Reserve decimals of {decimals} are more than 18")]
+    InvalidReserveDecimals { decimals: u8 },
+
+    #[error("Reserve denom must not be empty")]
+    InvalidReserveDenom {},
+
+    #[error("Supply cap must be more than zero")]
+    InvalidSupplyCap {},
+
+    #[error("Fee of {fee_bps} bps is more than 10000 bps")]
+    InvalidFee { fee_bps: u64 },
+
+    #[error("Fee split weights must add up to more than zero")]
+    InvalidFeeSplits {},
+
+    #[error("Payment is not in the reserve asset")]
+    WrongReserveAsset {},
+
+    #[error("Supply cap reached")]
+    SupplyCapReached {},
+
+    #[error("Hatch price must be more than zero")]
+    InvalidHatchPrice {},
+
+    #[error("Only allowlisted addresses can buy during the hatch")]
+    NotAllowlisted {},
+
+    #[error("Selling is not possible during the hatch")]
+    HatchInProgress {},
+
+    #[error("The hatch is already over")]
+    HatchOver {},
+
+    #[error("Curve slope or value must be more than zero")]
+    InvalidCurveSlope {},
+
+    #[error("Curve scale of {scale} is more than 18")]
+    InvalidCurveScale { scale: u32 },
+
+    #[error("Invalid curve: {reason}")]
+    InvalidCurve { reason: String },
+
+    #[error("New curve needs {required} reserve to back the supply, only {available} is bonded")]
+    InsufficientReserve {
+        required: Uint128,
+        available: Uint128,
+    },
+
+    #[error("Reserve ratio must be between 1 and 10000 bps, exit tribute at most 10000 bps")]
+    InvalidFunding {},
+
+    #[error("Funding pool only holds {available}")]
+    InsufficientFunding { available: Uint128 },
+
+    #[error("No admin transfer is pending")]
+    NoPendingAdmin {},
+
+    #[error("Buys are paused")]
+    BuysPaused {},
+
+    #[error("Sells are paused")]
+    SellsPaused {},
+
+    #[error("BurnFrom refunds go to the owner, they can't have another recipient")]
+    BurnFromRecipient {},
+
+    #[error("Deadline expired")]
+    DeadlineExpired {},
+
+    #[error("Buy would mint {tokens_out} tokens, less than the minimum of {min_tokens_out}")]
+    MinTokensOut {
+        min_tokens_out: Uint128,
+        tokens_out: Uint128,
+    },
+
+    #[error(
+        "Burn would release {reserve_out} reserve, less than the minimum of {min_reserve_out}"
+    )]
+    MinReserveOut {
+        min_reserve_out: Uint128,
+        reserve_out: Uint128,
+    },
 }
\ No newline at end of file
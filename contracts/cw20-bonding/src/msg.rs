@@ -1,9 +1,16 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::curves::{decimal, Constant, Curve, DecimalPlaces, Linear, SquareRoot, Exponential};
-use cosmwasm_std::{Binary, Decimal, Uint128, Addr};
-use cw20::Expiration;
+use crate::curves::{
+    decimal, Constant, Curve, DecimalPlaces, Exponential, Linear, Piecewise, Power, Segment,
+    Sigmoid, SquareRoot,
+};
+use cosmwasm_std::{
+    coins, to_binary, Addr, BankMsg, Binary, CosmosMsg, Decimal, Decimal256, StdResult, Uint128,
+    WasmMsg,
+};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg, Expiration, Logo};
+use cw20_base::msg::InstantiateMarketingInfo;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
@@ -15,8 +22,8 @@ pub struct InstantiateMsg {
     /// If it is eg. BTC, where a balance of 10^8 means 1 BTC, then use 8 here.
     pub decimals: u8,
 
-    /// this is the reserve token denom (only support native for now)
-    pub reserve_denom: String,
+    /// this is the reserve token, either a native denom or a cw20 contract
+    pub reserve_asset: ReserveAsset,
     /// number of decimal places for the reserve token, needed for proper curve math.
     /// Same format as decimals above, eg. if it is uatom, where 1 unit is 10^-6 ATOM, use 6 here
     pub reserve_decimals: u8,
@@ -26,10 +33,179 @@ pub struct InstantiateMsg {
     /// write a custom `instantiate`, and then dispatch `your::execute` -> `cw20_bonding::do_execute`
     /// with your custom curve as a parameter (and same with `query` -> `do_query`)
     pub curve_type: CurveType,
-    /// buy fee
-    pub buy_fee: u64,
-    /// buy fee will go to the treasury address
-    pub treasury: Addr
+    /// buy fee in basis points (1/10000) of the reserve paid in
+    pub buy_fee_bps: u64,
+    /// sell fee in basis points (1/10000) of the reserve released
+    pub sell_fee_bps: u64,
+    /// fees go to the treasury address, unless `fee_splits` says otherwise
+    pub treasury: Addr,
+    /// split the fees between several recipients by weight. Empty sends everything to treasury
+    pub fee_splits: Vec<FeeSplit>,
+    /// what happens to the reserve released by burning without refund
+    pub unrefunded_reserve: UnrefundedReserve,
+    /// who gets the reserve released by a `BurnFrom` with refund
+    pub burn_from_payout: BurnFromPayout,
+    /// maximum supply, buys that would go over it are partially refunded
+    pub supply_cap: Option<Uint128>,
+    /// start with a closed hatch phase before opening the curve to everyone
+    pub hatch: Option<HatchParams>,
+    /// send part of every buy and sell to a funding pool (augmented bonding curve)
+    pub funding: Option<FundingParams>,
+    /// can update the config and pause trading, defaults to the instantiator
+    pub admin: Option<String>,
+    /// cw20 marketing info, the `marketing` address can update it later
+    pub marketing: Option<InstantiateMarketingInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {
+    /// Admin of the migrated contract, required when migrating from 0.1 which had none
+    pub admin: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HatchParams {
+    /// fixed price in reserve per supply token, normalized like the spot price. It must stay
+    /// above the curve's price, the difference goes to the treasury
+    pub price: Decimal,
+    /// open the curve once this much reserve (after fees) is raised. None waits for the admin
+    pub raise_target: Option<Uint128>,
+    /// the only addresses that can buy during the hatch
+    pub allowlist: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FundingParams {
+    /// part of each buy, in basis points, that backs the curve. The rest goes to the funding pool
+    pub reserve_ratio_bps: u64,
+    /// part of the released reserve, in basis points, that sellers leave in the funding pool
+    pub exit_tribute_bps: u64,
+    /// the only address that can withdraw from the funding pool
+    pub beneficiary: Addr,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SalePhase {
+    /// only allowlisted addresses can buy, at a fixed price. Nobody can sell
+    Hatch {
+        price: Decimal,
+        raise_target: Option<Uint128>,
+        raised: Uint128,
+    },
+    /// everyone trades on the curve
+    #[default]
+    Open,
+}
+
+/// Where the reserve released by a `Burn` or `BurnFrom` without refund goes
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UnrefundedReserve {
+    /// stays in the contract as donated reserve, paid out pro rata to the remaining holders
+    /// when they sell
+    #[default]
+    Donate,
+    /// is sent to the treasury
+    Treasury,
+}
+
+/// Who gets the reserve released by a `BurnFrom` with refund
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BurnFromPayout {
+    /// the spender, or the `recipient` they choose
+    #[default]
+    Spender,
+    /// the owner of the burned tokens, the spender can't pick a `recipient`
+    Owner,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeRecipient {
+    /// the configured treasury address
+    Treasury,
+    /// any other address, eg. the creator
+    Address { address: Addr },
+    /// burn the reserve tokens
+    Burn,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TradeSide {
+    Buy,
+    Sell,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeSplit {
+    pub recipient: FeeRecipient,
+    /// share of the fee is weight / sum of all weights
+    pub weight: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReserveAsset {
+    /// native coins, paid with the `Buy` message
+    Native { denom: String },
+    /// cw20 tokens, paid by sending them with a `ReceiveMsg::Buy` hook
+    Cw20 { address: Addr },
+}
+
+impl UnrefundedReserve {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UnrefundedReserve::Donate => "donate",
+            UnrefundedReserve::Treasury => "treasury",
+        }
+    }
+}
+
+impl ReserveAsset {
+    /// Message paying `amount` of the reserve from this contract to `recipient`
+    pub fn transfer_msg(
+        &self,
+        recipient: impl Into<String>,
+        amount: Uint128,
+    ) -> StdResult<CosmosMsg> {
+        let msg = match self {
+            ReserveAsset::Native { denom } => BankMsg::Send {
+                to_address: recipient.into(),
+                amount: coins(amount.u128(), denom),
+            }
+            .into(),
+            ReserveAsset::Cw20 { address } => WasmMsg::Execute {
+                contract_addr: address.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.into(),
+                    amount,
+                })?,
+                funds: vec![],
+            }
+            .into(),
+        };
+        Ok(msg)
+    }
+
+    /// Message destroying `amount` of the reserve held by this contract
+    pub fn burn_msg(&self, amount: Uint128) -> StdResult<CosmosMsg> {
+        let msg = match self {
+            ReserveAsset::Native { denom } => BankMsg::Burn {
+                amount: coins(amount.u128(), denom),
+            }
+            .into(),
+            ReserveAsset::Cw20 { address } => WasmMsg::Execute {
+                contract_addr: address.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Burn { amount })?,
+                funds: vec![],
+            }
+            .into(),
+        };
+        Ok(msg)
+    }
 }
 
 pub type CurveFn = Box<dyn Fn(DecimalPlaces) -> Box<dyn Curve>>;
@@ -44,7 +220,34 @@ pub enum CurveType {
     /// SquareRoot returns `slope * 10^-scale * supply^0.5` as spot price
     SquareRoot { slope: Uint128, scale: u32 },
     /// Exponential
-    Exponential { slope: Uint128, scale: u32 }
+    Exponential { slope: Uint128, scale: u32 },
+    /// Power returns `slope * 10^-scale * supply^(exponent_num / exponent_den)` as spot price
+    Power {
+        slope: Uint128,
+        scale: u32,
+        exponent_num: u32,
+        exponent_den: u32,
+    },
+    /// Piecewise prices each segment linearly from its `start` (in supply units) up to the next one.
+    /// Segments must start at 0 and be sorted, prices may jump between segments
+    Piecewise { segments: Vec<PiecewiseSegment> },
+    /// Sigmoid returns `max_price * 10^-scale / 2 * (1 + (supply - inflection) / sqrt(steepness + (supply - inflection)^2))`
+    /// as spot price, flattening towards max price. `inflection` is in whole tokens, `steepness` in whole tokens squared
+    Sigmoid {
+        max_price: Uint128,
+        scale: u32,
+        inflection: Uint128,
+        steepness: Uint128,
+    },
+}
+
+/// Spot price in a segment is `(price + slope * (supply - start)) * 10^-scale`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PiecewiseSegment {
+    pub start: Uint128,
+    pub price: Uint128,
+    pub slope: Uint128,
+    pub scale: u32,
 }
 
 impl CurveType {
@@ -74,6 +277,52 @@ impl CurveType {
                 };
                 Box::new(calc)
             }
+            CurveType::Power {
+                slope,
+                scale,
+                exponent_num,
+                exponent_den,
+            } => {
+                let calc = move |places| -> Box<dyn Curve> {
+                    Box::new(Power::new(
+                        decimal(slope, scale),
+                        exponent_num,
+                        exponent_den,
+                        places,
+                    ))
+                };
+                Box::new(calc)
+            }
+            CurveType::Piecewise { segments } => {
+                let segments: Vec<Segment> = segments
+                    .into_iter()
+                    .map(|s| Segment {
+                        start: s.start,
+                        price: decimal(s.price, s.scale),
+                        slope: decimal(s.slope, s.scale),
+                    })
+                    .collect();
+                let calc = move |places| -> Box<dyn Curve> {
+                    Box::new(Piecewise::new(segments.clone(), places))
+                };
+                Box::new(calc)
+            }
+            CurveType::Sigmoid {
+                max_price,
+                scale,
+                inflection,
+                steepness,
+            } => {
+                let calc = move |places| -> Box<dyn Curve> {
+                    Box::new(Sigmoid::new(
+                        decimal(max_price, scale),
+                        decimal(inflection, 0),
+                        decimal(steepness, 0),
+                        places,
+                    ))
+                };
+                Box::new(calc)
+            }
         }
     }
 }
@@ -82,13 +331,37 @@ impl CurveType {
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
     /// Buy will attempt to purchase as many supply tokens as possible.
-    /// You must send only reserve tokens in that message
-    Buy {},
+    /// You must send only reserve tokens in that message. Only for a native reserve
+    Buy {
+        /// who gets the minted tokens, defaults to the sender
+        recipient: Option<String>,
+        /// fail if the curve mints fewer tokens than this
+        min_tokens_out: Option<Uint128>,
+        /// fail if the message is executed after this height or time
+        deadline: Option<Expiration>,
+    },
+    /// Same as `Buy`, but the minted tokens are sent to `contract` like a CW20 `Send`,
+    /// triggering `msg` on it with the sender as the owner. Only for a native reserve
+    BuyAndSend {
+        contract: String,
+        msg: Binary,
+        min_tokens_out: Option<Uint128>,
+        deadline: Option<Expiration>,
+    },
 
     /// Implements CW20. Transfer is a base message to move tokens to another account without triggering actions
     Transfer { recipient: String, amount: Uint128 },
     /// Implements CW20. Burn is a base message to destroy tokens forever
-    Burn { amount: Uint128, refund: Option<bool> },
+    Burn {
+        amount: Uint128,
+        refund: Option<bool>,
+        /// who gets the refund, defaults to the sender
+        recipient: Option<String>,
+        /// fail if the curve releases less reserve than this
+        min_reserve_out: Option<Uint128>,
+        /// fail if the message is executed after this height or time
+        deadline: Option<Expiration>,
+    },
     /// Implements CW20.  Send is a base message to transfer tokens to a contract and trigger an action
     /// on the receiving contract.
     Send {
@@ -128,7 +401,93 @@ pub enum ExecuteMsg {
         msg: Binary,
     },
     /// Implements CW20 "approval" extension. Destroys tokens forever
-    BurnFrom { owner: String, amount: Uint128, refund: Option<bool> },
+    BurnFrom {
+        owner: String,
+        amount: Uint128,
+        refund: Option<bool>,
+        /// who gets the refund, defaults to the spender. Not allowed if `BurnFromPayout::Owner`
+        recipient: Option<String>,
+        /// fail if the curve releases less reserve than this
+        min_reserve_out: Option<Uint128>,
+        /// fail if the message is executed after this height or time
+        deadline: Option<Expiration>,
+    },
+    /// Implements CW20 "marketing" extension. If authorized, updates marketing metadata.
+    /// Setting None/null for any of these will leave it unchanged.
+    /// Setting Some("") will clear this field on the contract storage
+    UpdateMarketing {
+        /// A URL pointing to the project behind this token.
+        project: Option<String>,
+        /// A longer description of the token and it's utility. Designed for tooltips or such
+        description: Option<String>,
+        /// The address (if any) who can update this data structure
+        marketing: Option<String>,
+    },
+    /// Implements CW20 "marketing" extension. If set as the "marketing" role on the contract,
+    /// upload a new URL, SVG, or PNG for the token
+    UploadLogo(Logo),
+
+    /// Buy with a cw20 reserve, the cw20 contract calls this when tokens are sent with `ReceiveMsg`
+    Receive(Cw20ReceiveMsg),
+
+    /// Admin only. Changes the given fields of the config, leaving the others as they are
+    UpdateConfig {
+        buy_fee_bps: Option<u64>,
+        sell_fee_bps: Option<u64>,
+        treasury: Option<Addr>,
+        fee_splits: Option<Vec<FeeSplit>>,
+        unrefunded_reserve: Option<UnrefundedReserve>,
+        burn_from_payout: Option<BurnFromPayout>,
+    },
+    /// Admin only. Replaces the curve, keeping the current supply and reserve. The new curve may
+    /// not need more reserve than there is to buy back the supply, any extra is donated to the
+    /// holders. With `fit_reserve` the slope (or value) is first scaled so the new curve prices
+    /// the supply at the current reserve, raising `scale` up to 18 as needed. Piecewise and
+    /// exponential curves can't be fitted. Derived contracts with their own `CurveFn` ignore this
+    UpdateCurve {
+        curve_type: CurveType,
+        #[serde(default)]
+        fit_reserve: bool,
+    },
+    /// Admin only. Proposes a new admin, who has to accept it with `AcceptAdmin`
+    TransferAdmin { new_admin: String },
+    /// Pending admin only. Completes the admin transfer
+    AcceptAdmin {},
+    /// Admin only. Halts buys or sells, or both if side is not set
+    Pause { side: Option<TradeSide> },
+    /// Admin only. Resumes buys or sells, or both if side is not set
+    Unpause { side: Option<TradeSide> },
+    /// Admin only. Adds or removes addresses allowed to buy during the hatch
+    UpdateHatchAllowlist {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+    /// Admin only. Ends the hatch and opens the curve to everyone
+    OpenTrading {},
+    /// Funding beneficiary only. Withdraws from the funding pool, everything if amount is not set
+    WithdrawFunding { amount: Option<Uint128> },
+    /// Admin only. Compares the reserve balance of the contract with the tracked reserve
+    /// and reports the difference, see `ReconcileResponse`
+    Reconcile {},
+}
+
+/// Hook messages for cw20 reserve tokens sent to this contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    /// Same as `ExecuteMsg::Buy`, paying with the sent tokens
+    Buy {
+        recipient: Option<String>,
+        min_tokens_out: Option<Uint128>,
+        deadline: Option<Expiration>,
+    },
+    /// Same as `ExecuteMsg::BuyAndSend`, paying with the sent tokens
+    BuyAndSend {
+        contract: String,
+        msg: Binary,
+        min_tokens_out: Option<Uint128>,
+        deadline: Option<Expiration>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -136,6 +495,29 @@ pub enum ExecuteMsg {
 pub enum QueryMsg {
     /// Returns the reserve and supply quantities, as well as the spot price to buy 1 token
     CurveInfo {},
+    /// Returns the outcome of a `Buy` paying `reserve_amount` (fee included) right now
+    SimulateBuy { reserve_amount: Uint128 },
+    /// Returns the outcome of a `Burn` of `supply_amount` tokens with refund right now
+    SimulateSell { supply_amount: Uint128 },
+    /// Returns how much reserve (fee included) a `Buy` needs to mint `supply_amount` tokens
+    ReverseSimulateBuy { supply_amount: Uint128 },
+    /// Returns how many tokens need to be burned to get `reserve_amount` back
+    ReverseSimulateSell { reserve_amount: Uint128 },
+    /// Returns the fee config, admin and pause state
+    Config {},
+    /// Returns the sale phase and supply cap
+    SalePhase {},
+    /// Returns whether the address can buy during the hatch
+    HatchAllowlisted { address: String },
+    /// Returns the funding pool balance and parameters
+    FundingPool {},
+    /// Returns the time weighted average spot price over the last `window_seconds`
+    Twap { window_seconds: u64 },
+    /// Returns the recorded price observations after the `start_after` timestamp, oldest first
+    PriceHistory {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
 
     /// Implements CW20. Returns the current balance of the given address, 0 if unset.
     Balance { address: String },
@@ -144,6 +526,35 @@ pub enum QueryMsg {
     /// Implements CW20 "allowance" extension.
     /// Returns how much spender can use from owner account, 0 if unset.
     Allowance { owner: String, spender: String },
+    /// Implements CW20 "mintable" extension. The minter is always this contract,
+    /// returns it with the supply cap.
+    Minter {},
+    /// Implements CW20 "enumerable" extension.
+    /// Returns all allowances this owner has approved. Supports pagination.
+    AllAllowances {
+        owner: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Implements CW20 "enumerable" extension.
+    /// Returns all allowances this spender has been granted, by owner. Supports pagination.
+    AllSpenderAllowances {
+        spender: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Implements CW20 "enumerable" extension.
+    /// Returns all accounts that have balances. Supports pagination.
+    AllAccounts {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Implements CW20 "marketing" extension. Returns more metadata on the contract to display
+    /// in the client: description, logo, project url, etc.
+    MarketingInfo {},
+    /// Implements CW20 "marketing" extension. Downloads the embedded logo data (if stored on chain).
+    /// Errors if no logo data is stored for this contract.
+    DownloadLogo {},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -152,6 +563,89 @@ pub struct CurveInfoResponse {
     pub reserve: Uint128,
     // how many supply tokens have been issued
     pub supply: Uint128,
+    // reserve left by burns without refund, on top of the curve's reserve
+    pub donated_reserve: Uint128,
     pub spot_price: Decimal,
-    pub reserve_denom: String,
-}
\ No newline at end of file
+    pub reserve_asset: ReserveAsset,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConfigResponse {
+    pub buy_fee_bps: u64,
+    pub sell_fee_bps: u64,
+    pub treasury: Addr,
+    pub fee_splits: Vec<FeeSplit>,
+    pub unrefunded_reserve: UnrefundedReserve,
+    pub burn_from_payout: BurnFromPayout,
+    pub admin: Option<Addr>,
+    pub pending_admin: Option<Addr>,
+    pub buys_paused: bool,
+    pub sells_paused: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SalePhaseResponse {
+    pub phase: SalePhase,
+    pub supply_cap: Option<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FundingPoolResponse {
+    pub balance: Uint128,
+    pub funding: Option<FundingParams>,
+}
+
+/// The spot price after the last trade in a block
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceObservation {
+    // block time in seconds
+    pub timestamp: u64,
+    pub spot_price: Decimal,
+    // sum of spot_price * seconds since the first observation, up to timestamp
+    pub cumulative_price: Decimal256,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TwapResponse {
+    pub twap: Decimal,
+    pub window_seconds: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceHistoryResponse {
+    pub observations: Vec<PriceObservation>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HatchAllowlistedResponse {
+    pub allowlisted: bool,
+}
+
+/// Set as data on the `Reconcile` response
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReconcileResponse {
+    /// reserve tokens held by the contract
+    pub balance: Uint128,
+    /// reserve, donated reserve and funding pool in the curve state
+    pub tracked: Uint128,
+    /// balance above tracked, eg. tokens sent to the contract directly
+    pub surplus: Uint128,
+    /// tracked above balance, this should never happen
+    pub shortfall: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulationResponse {
+    /// reserve tokens paid (buy, fee included) or received (sell, fee deducted)
+    pub reserve_amount: Uint128,
+    /// supply tokens minted (buy) or burned (sell)
+    pub supply_amount: Uint128,
+    /// reserve taken as fee, on top of reserve_amount for sells
+    pub fee: Uint128,
+    /// reserve that goes to the funding pool, on top of reserve_amount for sells
+    pub funding: Uint128,
+    /// reserve_amount / supply_amount, normalized like spot_price
+    pub average_price: Decimal,
+    /// spot price after the trade
+    pub spot_price: Decimal,
+}
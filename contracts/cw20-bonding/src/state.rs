@@ -1,46 +1,125 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Uint128, Addr};
-use cw_storage_plus::Item;
+use cosmwasm_std::{Addr, Empty, Uint128};
+use cw_storage_plus::{Item, Map};
 
 use crate::curves::DecimalPlaces;
-use crate::msg::CurveType;
+use crate::msg::{
+    BurnFromPayout, CurveType, FeeSplit, FundingParams, PriceObservation, ReserveAsset, SalePhase,
+    UnrefundedReserve,
+};
 
 /// Supply is dynamic and tracks the current supply of staked and ERC20 tokens.
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct CurveState {
     /// reserve is how many native tokens exist bonded to the validator
     pub reserve: Uint128,
     /// supply is how many tokens this contract has issued
     pub supply: Uint128,
+    /// reserve released by burns without refund, paid out pro rata on sells
+    #[serde(default)]
+    pub donated_reserve: Uint128,
+    /// reserve set aside for the funding beneficiary, it doesn't back the curve
+    #[serde(default)]
+    pub funding_pool: Uint128,
 
-    // the denom of the reserve token
-    pub reserve_denom: String,
+    // the native denom or cw20 contract of the reserve token
+    pub reserve_asset: ReserveAsset,
 
     // how to normalize reserve and supply
     pub decimals: DecimalPlaces,
+
+    /// maximum supply, also set as the cw20 minter cap
+    #[serde(default)]
+    pub supply_cap: Option<Uint128>,
+    #[serde(default)]
+    pub phase: SalePhase,
+    #[serde(default)]
+    pub funding: Option<FundingParams>,
 }
 
 impl CurveState {
-    pub fn new(reserve_denom: String, decimals: DecimalPlaces) -> Self {
+    pub fn new(reserve_asset: ReserveAsset, decimals: DecimalPlaces) -> Self {
         CurveState {
             reserve: Uint128::zero(),
             supply: Uint128::zero(),
-            reserve_denom,
+            donated_reserve: Uint128::zero(),
+            funding_pool: Uint128::zero(),
+            reserve_asset,
             decimals,
+            supply_cap: None,
+            phase: SalePhase::Open,
+            funding: None,
         }
     }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
-    pub buy_fee: u64,
+    /// fee on buys, in basis points of the payment
+    pub buy_fee_bps: u64,
+    /// fee on sells, in basis points of the released reserve
+    pub sell_fee_bps: u64,
     pub treasury: Addr,
+    pub fee_splits: Vec<FeeSplit>,
+    #[serde(default)]
+    pub unrefunded_reserve: UnrefundedReserve,
+    #[serde(default)]
+    pub burn_from_payout: BurnFromPayout,
 }
 
 pub const CURVE_STATE: Item<CurveState> = Item::new("curve_state");
 
 pub const CURVE_TYPE: Item<CurveType> = Item::new("curve_type");
 
-pub const CONFIG: Item<Config> = Item::new("config");
\ No newline at end of file
+/// Trading halted by the admin, each side separately
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct PauseState {
+    pub buys: bool,
+    pub sells: bool,
+}
+
+pub const CONFIG: Item<Config> = Item::new("config");
+
+pub const ADMIN: Item<Addr> = Item::new("admin");
+
+pub const PENDING_ADMIN: Item<Addr> = Item::new("pending_admin");
+
+pub const PAUSED: Item<PauseState> = Item::new("paused");
+
+/// addresses that can buy during the hatch phase
+pub const HATCH_ALLOWLIST: Map<&Addr, Empty> = Map::new("hatch_allowlist");
+
+/// State as stored by 0.1, only read by `migrate`
+pub mod v0_1 {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct CurveState {
+        pub reserve: Uint128,
+        pub supply: Uint128,
+        pub reserve_denom: String,
+        pub decimals: DecimalPlaces,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct Config {
+        /// in whole percent
+        pub buy_fee: u64,
+        pub treasury: Addr,
+    }
+
+    pub const CURVE_STATE: Item<CurveState> = Item::new("curve_state");
+
+    pub const CONFIG: Item<Config> = Item::new("config");
+}
+
+/// how many price observations are kept for `Twap` and `PriceHistory`
+pub const PRICE_HISTORY_SIZE: u64 = 256;
+
+/// how many observations have ever been recorded, the newest one is at `count - 1`
+pub const OBSERVATION_COUNT: Item<u64> = Item::new("observation_count");
+
+/// ring buffer of price observations, observation `n` is kept in slot `n % PRICE_HISTORY_SIZE`
+pub const OBSERVATIONS: Map<u64, PriceObservation> = Map::new("observations");
@@ -1,15 +1,35 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdResult};
-
+use cosmwasm_std::{
+    to_binary, Addr, Binary, Deps, DepsMut, Empty, Env, Event, MessageInfo, Order, Response,
+    StdResult,
+};
+use cw2::{get_contract_version, set_contract_version, ContractVersion};
+use cw721::{Cw721Execute, TokensResponse};
+use cw721_base::Cw721Contract;
+use cw_storage_plus::Bound;
+use semver::Version;
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
+use crate::msg::{
+    ExecuteExt, ExecuteMsg, Extension, FrozenStatusResponse, InstantiateMsg, LockStatusResponse,
+    MigrateMsg, ProvenanceResponse, QueryExt, QueryMsg,
+};
+use crate::state::{
+    update_metadata_index, ProvenanceRecord, FROZEN_TOKENS, LOCKED_TOKENS, PROVENANCE,
+    TOKENS_BY_EDITION, TOKENS_BY_SIZE,
+};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:cw721-suit";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// cw721-base contract with the suit extension messages plugged in
+pub type Cw721SuitContract<'a> = Cw721Contract<'a, Extension, Empty, ExecuteExt, QueryExt>;
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 100;
+
 /// Handling contract instantiation
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -17,23 +37,52 @@ pub fn instantiate(
     env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
-) -> Result<Response, cosmwasm_std::StdError> {
-    cw721_base::entry::instantiate(deps, env, info, msg)
+) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(Cw721SuitContract::default().instantiate(deps, env, info, msg)?)
 }
 
 /// Handling contract migration
 /// To make a contract migratable, you need
 /// - this entry_point implemented
 /// - only contract admin can migrate, so admin has to be set at contract initiation time
-/// Handling contract execution
+///
+/// Accepts older cw721-suit versions as well as plain cw721-base 0.16 / 0.17 deployments whose
+/// tokens were minted with an `Empty` or `null` extension. Any other extension doesn't
+/// deserialize as `Metadata`, so such deployments can't be migrated.
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
-    match msg {
-        // Find matched incoming message variant and execute them with your custom logic.
-        //
-        // With `Response` type, it is possible to dispatch message to invoke external logic.
-        // See: https://github.com/CosmWasm/cosmwasm/blob/main/SEMANTICS.md#dispatching-messages
+pub fn migrate(mut deps: DepsMut, env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let ContractVersion { contract, version } = get_contract_version(deps.storage)?;
+
+    let mut res = Response::new();
+    if contract == cw721_base::CONTRACT_NAME {
+        // let cw721-base move the minter into cw-ownable, 0.17 needs no storage changes
+        if version != cw721_base::CONTRACT_VERSION {
+            res = Cw721SuitContract::migrate(deps.branch(), env)?;
+        }
+    } else if contract == CONTRACT_NAME {
+        let stored: Version = version.parse()?;
+        let current: Version = CONTRACT_VERSION.parse()?;
+        if stored > current {
+            return Err(ContractError::CannotMigrate {
+                previous_contract: contract,
+                previous_version: version,
+            });
+        }
+    } else {
+        return Err(ContractError::CannotMigrate {
+            previous_contract: contract,
+            previous_version: version,
+        });
     }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(res
+        .add_attribute("action", "migrate")
+        .add_attribute("previous_contract", contract)
+        .add_attribute("previous_version", version)
+        .add_attribute("new_version", CONTRACT_VERSION))
 }
 
 /// Handling contract execution
@@ -43,22 +92,846 @@ pub fn execute(
     env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
-) -> Result<Response, cw721_base::ContractError> {
-    cw721_base::entry::execute(deps, env, info, msg)
+) -> Result<Response, ContractError> {
+    let tract = Cw721SuitContract::default();
+    match msg {
+        ExecuteMsg::Extension { msg } => match msg {
+            ExecuteExt::Lock { token_id } => execute_lock(deps, info, token_id),
+            ExecuteExt::Unlock { token_id } => execute_unlock(deps, info, token_id),
+            ExecuteExt::UpdateTokenMetadata {
+                token_id,
+                extension,
+                token_uri,
+            } => execute_update_token_metadata(deps, info, token_id, extension, token_uri),
+            ExecuteExt::FreezeMetadata { token_id } => {
+                execute_freeze_metadata(deps, info, token_id)
+            }
+            ExecuteExt::Reissue {
+                old_token_id,
+                new_owner,
+                reason,
+            } => execute_reissue(deps, env, info, old_token_id, new_owner, reason),
+        },
+        ExecuteMsg::Mint {
+            token_id,
+            owner,
+            token_uri,
+            extension,
+        } => execute_mint(deps, info, token_id, owner, token_uri, extension),
+        // locked tokens stay with their owner until the minter unlocks them
+        ExecuteMsg::TransferNft { ref token_id, .. } | ExecuteMsg::SendNft { ref token_id, .. } => {
+            assert_not_locked(deps.as_ref(), token_id)?;
+            Ok(tract.execute(deps, env, info, msg)?)
+        }
+        ExecuteMsg::Burn { token_id } => execute_burn(deps, env, info, token_id),
+        _ => Ok(tract.execute(deps, env, info, msg)?),
+    }
+}
+
+pub fn execute_mint(
+    mut deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+    owner: String,
+    token_uri: Option<String>,
+    extension: Extension,
+) -> Result<Response, ContractError> {
+    let res = Cw721SuitContract::default().mint(
+        deps.branch(),
+        info,
+        token_id.clone(),
+        owner,
+        token_uri,
+        extension.clone(),
+    )?;
+    update_metadata_index(deps.storage, &token_id, &None, &extension)?;
+    Ok(res)
+}
+
+pub fn execute_burn(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let tract = Cw721SuitContract::default();
+    let token = tract.tokens.load(deps.storage, &token_id)?;
+    let res = tract.burn(deps.branch(), env, info, token_id.clone())?;
+
+    // drop the suit specific state along with the token
+    LOCKED_TOKENS.remove(deps.storage, &token_id);
+    FROZEN_TOKENS.remove(deps.storage, &token_id);
+    update_metadata_index(deps.storage, &token_id, &token.extension, &None)?;
+    Ok(res)
+}
+
+pub fn execute_lock(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    assert_minter(deps.as_ref(), &info.sender)?;
+    // make sure the token exists
+    Cw721SuitContract::default()
+        .tokens
+        .load(deps.storage, &token_id)?;
+
+    LOCKED_TOKENS.save(deps.storage, &token_id, &true)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "lock")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id))
+}
+
+pub fn execute_unlock(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    assert_minter(deps.as_ref(), &info.sender)?;
+    Cw721SuitContract::default()
+        .tokens
+        .load(deps.storage, &token_id)?;
+
+    LOCKED_TOKENS.remove(deps.storage, &token_id);
+
+    Ok(Response::new()
+        .add_attribute("action", "unlock")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id))
+}
+
+pub fn execute_update_token_metadata(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+    extension: Extension,
+    token_uri: Option<String>,
+) -> Result<Response, ContractError> {
+    assert_minter(deps.as_ref(), &info.sender)?;
+    if FROZEN_TOKENS.has(deps.storage, &token_id) {
+        return Err(ContractError::MetadataFrozen { token_id });
+    }
+
+    let tract = Cw721SuitContract::default();
+    let mut token = tract.tokens.load(deps.storage, &token_id)?;
+    update_metadata_index(deps.storage, &token_id, &token.extension, &extension)?;
+    token.extension = extension;
+    token.token_uri = token_uri;
+    tract.tokens.save(deps.storage, &token_id, &token)?;
+
+    // emit the new metadata so indexers don't have to query it back
+    let mut event = Event::new("suit_metadata_update")
+        .add_attribute("token_id", &token_id)
+        .add_attribute("token_uri", token.token_uri.unwrap_or_default());
+    if let Some(metadata) = token.extension {
+        event = event
+            .add_attribute("size", metadata.size.unwrap_or_default())
+            .add_attribute(
+                "edition",
+                metadata.edition.map(|e| e.to_string()).unwrap_or_default(),
+            )
+            .add_attribute(
+                "tracking_status",
+                metadata.tracking_status.unwrap_or_default(),
+            )
+            .add_attribute("tailor_notes", metadata.tailor_notes.unwrap_or_default());
+    }
+
+    Ok(Response::new()
+        .add_event(event)
+        .add_attribute("action", "update_token_metadata")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id))
+}
+
+pub fn execute_freeze_metadata(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    assert_minter(deps.as_ref(), &info.sender)?;
+    Cw721SuitContract::default()
+        .tokens
+        .load(deps.storage, &token_id)?;
+    if FROZEN_TOKENS.has(deps.storage, &token_id) {
+        return Err(ContractError::MetadataFrozen { token_id });
+    }
+
+    FROZEN_TOKENS.save(deps.storage, &token_id, &true)?;
+
+    Ok(Response::new()
+        .add_event(Event::new("suit_metadata_freeze").add_attribute("token_id", &token_id))
+        .add_attribute("action", "freeze_metadata")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id))
+}
+
+pub fn execute_reissue(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    new_owner: Option<String>,
+    reason: Option<String>,
+) -> Result<Response, ContractError> {
+    assert_minter(deps.as_ref(), &info.sender)?;
+
+    let tract = Cw721SuitContract::default();
+    let token = tract.tokens.load(deps.storage, &token_id)?;
+
+    // the minter holds no approval on the token, so it is removed directly rather than through
+    // cw721-base `burn`, which only lets the owner or an operator burn
+    tract.tokens.remove(deps.storage, &token_id)?;
+    tract.decrement_tokens(deps.storage)?;
+    // the lock belonged to the old owner's fulfillment
+    LOCKED_TOKENS.remove(deps.storage, &token_id);
+
+    let new_owner = new_owner
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    match &new_owner {
+        // same serial, same garment: metadata, its index and freeze carry over
+        Some(new_owner) => {
+            tract.mint(
+                deps.branch(),
+                info.clone(),
+                token_id.clone(),
+                new_owner.to_string(),
+                token.token_uri,
+                token.extension,
+            )?;
+        }
+        None => {
+            FROZEN_TOKENS.remove(deps.storage, &token_id);
+            update_metadata_index(deps.storage, &token_id, &token.extension, &None)?;
+        }
+    }
+
+    let record = ProvenanceRecord {
+        previous_owner: token.owner.clone(),
+        reason,
+        burned_at: env.block.time,
+        reissued_to: new_owner.clone(),
+    };
+    PROVENANCE.update(deps.storage, &token_id, |records| -> StdResult<_> {
+        let mut records = records.unwrap_or_default();
+        records.push(record);
+        Ok(records)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "reissue")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id)
+        .add_attribute("previous_owner", token.owner)
+        .add_attribute("new_owner", new_owner.map(String::from).unwrap_or_default()))
+}
+
+/// Only the minter (the redeem contract) may manage suit specific state
+fn assert_minter(deps: Deps, sender: &Addr) -> Result<(), ContractError> {
+    let minter = Cw721SuitContract::default().minter(deps)?.minter;
+    if minter.as_deref() != Some(sender.as_str()) {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(())
+}
+
+fn assert_not_locked(deps: Deps, token_id: &str) -> Result<(), ContractError> {
+    if LOCKED_TOKENS.has(deps.storage, token_id) {
+        return Err(ContractError::Locked {
+            token_id: token_id.to_string(),
+        });
+    }
+    Ok(())
 }
 
 /// Handling contract query
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    cw721_base::entry::query(deps, env, msg)
+    match msg {
+        QueryMsg::Extension { msg } => match msg {
+            QueryExt::LockStatus { token_id } => to_binary(&query_lock_status(deps, token_id)?),
+            QueryExt::FrozenStatus { token_id } => to_binary(&query_frozen_status(deps, token_id)?),
+            QueryExt::Provenance { token_id } => to_binary(&query_provenance(deps, token_id)?),
+            QueryExt::TokensBySize {
+                size,
+                start_after,
+                limit,
+            } => to_binary(&query_tokens_by_size(deps, size, start_after, limit)?),
+            QueryExt::TokensByEdition {
+                edition,
+                start_after,
+                limit,
+            } => to_binary(&query_tokens_by_edition(deps, edition, start_after, limit)?),
+        },
+        _ => Cw721SuitContract::default().query(deps, env, msg),
+    }
 }
 
-/// Handling submessage reply.
-/// For more info on submessage and reply, see https://github.com/CosmWasm/cosmwasm/blob/main/SEMANTICS.md#submessages
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn reply(_deps: DepsMut, _env: Env, _msg: Reply) -> Result<Response, ContractError> {
-    // With `Response` type, it is still possible to dispatch message to invoke external logic.
-    // See: https://github.com/CosmWasm/cosmwasm/blob/main/SEMANTICS.md#dispatching-messages
+pub fn query_lock_status(deps: Deps, token_id: String) -> StdResult<LockStatusResponse> {
+    let locked = LOCKED_TOKENS.has(deps.storage, &token_id);
+    Ok(LockStatusResponse { token_id, locked })
+}
 
-    Ok(Response::new())
+pub fn query_frozen_status(deps: Deps, token_id: String) -> StdResult<FrozenStatusResponse> {
+    let frozen = FROZEN_TOKENS.has(deps.storage, &token_id);
+    Ok(FrozenStatusResponse { token_id, frozen })
+}
+
+pub fn query_provenance(deps: Deps, token_id: String) -> StdResult<ProvenanceResponse> {
+    let records = PROVENANCE
+        .may_load(deps.storage, &token_id)?
+        .unwrap_or_default();
+    Ok(ProvenanceResponse { token_id, records })
+}
+
+pub fn query_tokens_by_size(
+    deps: Deps,
+    size: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<TokensResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let tokens = TOKENS_BY_SIZE
+        .prefix(&size)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(TokensResponse { tokens })
+}
+
+pub fn query_tokens_by_edition(
+    deps: Deps,
+    edition: u32,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<TokensResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let tokens = TOKENS_BY_EDITION
+        .prefix(edition)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(TokensResponse { tokens })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::Metadata;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{attr, from_binary, to_binary};
+    use cw721::{NftInfoResponse, NumTokensResponse, OwnerOfResponse};
+    use cw_storage_plus::Item;
+    use serde::de::DeserializeOwned;
+
+    const MINTER: &str = "minter";
+    const OWNER: &str = "owner";
+    const OPERATOR: &str = "operator";
+    const RECIPIENT: &str = "recipient";
+
+    fn setup_test(deps: DepsMut) {
+        let msg = InstantiateMsg {
+            name: "Suits".to_string(),
+            symbol: "SUIT".to_string(),
+            minter: MINTER.to_string(),
+        };
+        instantiate(deps, mock_env(), mock_info("creator", &[]), msg).unwrap();
+    }
+
+    fn mint(deps: DepsMut, token_id: &str, extension: Extension) {
+        let msg = ExecuteMsg::Mint {
+            token_id: token_id.to_string(),
+            owner: OWNER.to_string(),
+            token_uri: None,
+            extension,
+        };
+        execute(deps, mock_env(), mock_info(MINTER, &[]), msg).unwrap();
+    }
+
+    fn execute_ext(
+        deps: DepsMut,
+        sender: &str,
+        msg: ExecuteExt,
+    ) -> Result<Response, ContractError> {
+        execute(
+            deps,
+            mock_env(),
+            mock_info(sender, &[]),
+            ExecuteMsg::Extension { msg },
+        )
+    }
+
+    fn query_ext<T: DeserializeOwned>(deps: Deps, msg: QueryExt) -> T {
+        from_binary(&query(deps, mock_env(), QueryMsg::Extension { msg }).unwrap()).unwrap()
+    }
+
+    fn owner_of(deps: Deps, token_id: &str) -> String {
+        let msg = QueryMsg::OwnerOf {
+            token_id: token_id.to_string(),
+            include_expired: None,
+        };
+        let res: OwnerOfResponse = from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap();
+        res.owner
+    }
+
+    fn nft_info(deps: Deps, token_id: &str) -> NftInfoResponse<Extension> {
+        let msg = QueryMsg::NftInfo {
+            token_id: token_id.to_string(),
+        };
+        from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+    }
+
+    fn suit(size: &str, edition: u32) -> Extension {
+        Some(Metadata {
+            size: Some(size.to_string()),
+            edition: Some(edition),
+            ..Metadata::default()
+        })
+    }
+
+    #[test]
+    fn locked_tokens_cannot_move() {
+        let mut deps = mock_dependencies();
+        setup_test(deps.as_mut());
+        mint(deps.as_mut(), "1", None);
+        let approve_all = ExecuteMsg::ApproveAll {
+            operator: OPERATOR.to_string(),
+            expires: None,
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(OWNER, &[]),
+            approve_all,
+        )
+        .unwrap();
+        let lock = || ExecuteExt::Lock {
+            token_id: "1".to_string(),
+        };
+        let unlock = || ExecuteExt::Unlock {
+            token_id: "1".to_string(),
+        };
+        let lock_status = |deps: Deps| -> bool {
+            let msg = QueryExt::LockStatus {
+                token_id: "1".to_string(),
+            };
+            query_ext::<LockStatusResponse>(deps, msg).locked
+        };
+        assert!(!lock_status(deps.as_ref()));
+
+        // only the minter can lock and unlock
+        let err = execute_ext(deps.as_mut(), OWNER, lock()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        execute_ext(deps.as_mut(), MINTER, lock()).unwrap();
+        assert!(lock_status(deps.as_ref()));
+        let err = execute_ext(deps.as_mut(), OWNER, unlock()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // neither the owner nor an approved operator can move a locked token
+        let transfer = ExecuteMsg::TransferNft {
+            recipient: RECIPIENT.to_string(),
+            token_id: "1".to_string(),
+        };
+        let send = ExecuteMsg::SendNft {
+            contract: RECIPIENT.to_string(),
+            token_id: "1".to_string(),
+            msg: to_binary("hook").unwrap(),
+        };
+        let locked = ContractError::Locked {
+            token_id: "1".to_string(),
+        };
+        for sender in [OWNER, OPERATOR] {
+            for msg in [transfer.clone(), send.clone()] {
+                let info = mock_info(sender, &[]);
+                let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+                assert_eq!(err, locked);
+            }
+        }
+        assert_eq!(owner_of(deps.as_ref(), "1"), OWNER);
+
+        // once unlocked the operator can transfer it again
+        execute_ext(deps.as_mut(), MINTER, unlock()).unwrap();
+        assert!(!lock_status(deps.as_ref()));
+        let info = mock_info(OPERATOR, &[]);
+        execute(deps.as_mut(), mock_env(), info, transfer).unwrap();
+        assert_eq!(owner_of(deps.as_ref(), "1"), RECIPIENT);
+    }
+
+    #[test]
+    fn metadata_indexes_follow_tokens() {
+        let mut deps = mock_dependencies();
+        setup_test(deps.as_mut());
+        mint(deps.as_mut(), "1", suit("40R", 1));
+        mint(deps.as_mut(), "2", suit("40R", 2));
+        mint(deps.as_mut(), "3", suit("42L", 1));
+        mint(deps.as_mut(), "4", None);
+        let by_size = |deps: Deps, size: &str, start_after: Option<&str>| -> Vec<String> {
+            let msg = QueryExt::TokensBySize {
+                size: size.to_string(),
+                start_after: start_after.map(String::from),
+                limit: None,
+            };
+            query_ext::<TokensResponse>(deps, msg).tokens
+        };
+        let by_edition = |deps: Deps, edition: u32| -> Vec<String> {
+            let msg = QueryExt::TokensByEdition {
+                edition,
+                start_after: None,
+                limit: None,
+            };
+            query_ext::<TokensResponse>(deps, msg).tokens
+        };
+        assert_eq!(by_size(deps.as_ref(), "40R", None), ["1", "2"]);
+        assert_eq!(by_size(deps.as_ref(), "40R", Some("1")), ["2"]);
+        assert_eq!(by_size(deps.as_ref(), "42L", None), ["3"]);
+        assert_eq!(by_edition(deps.as_ref(), 1), ["1", "3"]);
+        assert_eq!(by_edition(deps.as_ref(), 2), ["2"]);
+
+        // updating the metadata moves the token between index entries
+        let update = ExecuteExt::UpdateTokenMetadata {
+            token_id: "1".to_string(),
+            extension: suit("42L", 2),
+            token_uri: None,
+        };
+        execute_ext(deps.as_mut(), MINTER, update).unwrap();
+        assert_eq!(by_size(deps.as_ref(), "40R", None), ["2"]);
+        assert_eq!(by_size(deps.as_ref(), "42L", None), ["1", "3"]);
+        assert_eq!(by_edition(deps.as_ref(), 1), ["3"]);
+        assert_eq!(by_edition(deps.as_ref(), 2), ["1", "2"]);
+
+        // and clearing it or burning the token drops it from the indexes
+        let update = ExecuteExt::UpdateTokenMetadata {
+            token_id: "2".to_string(),
+            extension: None,
+            token_uri: None,
+        };
+        execute_ext(deps.as_mut(), MINTER, update).unwrap();
+        let burn = ExecuteMsg::Burn {
+            token_id: "3".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), burn).unwrap();
+        assert!(by_size(deps.as_ref(), "40R", None).is_empty());
+        assert_eq!(by_size(deps.as_ref(), "42L", None), ["1"]);
+        assert!(by_edition(deps.as_ref(), 1).is_empty());
+        assert_eq!(by_edition(deps.as_ref(), 2), ["1"]);
+    }
+
+    #[test]
+    fn frozen_metadata_cannot_be_updated() {
+        let mut deps = mock_dependencies();
+        setup_test(deps.as_mut());
+        mint(deps.as_mut(), "1", suit("40R", 1));
+        let update = |tracking_status: &str| ExecuteExt::UpdateTokenMetadata {
+            token_id: "1".to_string(),
+            extension: Some(Metadata {
+                tracking_status: Some(tracking_status.to_string()),
+                ..suit("40R", 1).unwrap()
+            }),
+            token_uri: Some("ipfs://suit/1".to_string()),
+        };
+        let freeze = || ExecuteExt::FreezeMetadata {
+            token_id: "1".to_string(),
+        };
+
+        // only the minter can update, and the new metadata is emitted
+        let err = execute_ext(deps.as_mut(), OWNER, update("shipped")).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let res = execute_ext(deps.as_mut(), MINTER, update("shipped")).unwrap();
+        assert_eq!(
+            res.events,
+            [Event::new("suit_metadata_update").add_attributes([
+                ("token_id", "1"),
+                ("token_uri", "ipfs://suit/1"),
+                ("size", "40R"),
+                ("edition", "1"),
+                ("tracking_status", "shipped"),
+                ("tailor_notes", ""),
+            ])]
+        );
+        let info = nft_info(deps.as_ref(), "1");
+        assert_eq!(info.token_uri.as_deref(), Some("ipfs://suit/1"));
+        assert_eq!(
+            info.extension.unwrap().tracking_status.as_deref(),
+            Some("shipped")
+        );
+
+        // freezing is minter only, emitted, and can't be repeated
+        let err = execute_ext(deps.as_mut(), OWNER, freeze()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let res = execute_ext(deps.as_mut(), MINTER, freeze()).unwrap();
+        assert_eq!(
+            res.events,
+            [Event::new("suit_metadata_freeze").add_attribute("token_id", "1")]
+        );
+        let msg = QueryExt::FrozenStatus {
+            token_id: "1".to_string(),
+        };
+        assert!(query_ext::<FrozenStatusResponse>(deps.as_ref(), msg).frozen);
+        let frozen = ContractError::MetadataFrozen {
+            token_id: "1".to_string(),
+        };
+        let err = execute_ext(deps.as_mut(), MINTER, freeze()).unwrap_err();
+        assert_eq!(err, frozen);
+
+        // after which the metadata stays as it was
+        let err = execute_ext(deps.as_mut(), MINTER, update("delivered")).unwrap_err();
+        assert_eq!(err, frozen);
+        let info = nft_info(deps.as_ref(), "1");
+        assert_eq!(
+            info.extension.unwrap().tracking_status.as_deref(),
+            Some("shipped")
+        );
+    }
+
+    #[test]
+    fn reissue_keeps_the_serial() {
+        let mut deps = mock_dependencies();
+        setup_test(deps.as_mut());
+        mint(deps.as_mut(), "1", suit("40R", 1));
+        let reissue = |new_owner: Option<&str>, reason: Option<&str>| ExecuteExt::Reissue {
+            old_token_id: "1".to_string(),
+            new_owner: new_owner.map(String::from),
+            reason: reason.map(String::from),
+        };
+        let token_id = || "1".to_string();
+        let provenance = |deps: Deps| -> Vec<ProvenanceRecord> {
+            let msg = QueryExt::Provenance {
+                token_id: token_id(),
+            };
+            query_ext::<ProvenanceResponse>(deps, msg).records
+        };
+
+        // only the minter can reissue
+        let err = execute_ext(deps.as_mut(), OWNER, reissue(Some(OWNER), None)).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // the lock is lifted, the freeze and the metadata carry over
+        execute_ext(
+            deps.as_mut(),
+            MINTER,
+            ExecuteExt::Lock {
+                token_id: token_id(),
+            },
+        )
+        .unwrap();
+        let freeze = ExecuteExt::FreezeMetadata {
+            token_id: token_id(),
+        };
+        execute_ext(deps.as_mut(), MINTER, freeze).unwrap();
+        let res = execute_ext(
+            deps.as_mut(),
+            MINTER,
+            reissue(Some(RECIPIENT), Some("damaged")),
+        )
+        .unwrap();
+        assert_eq!(res.attributes[3], attr("previous_owner", OWNER));
+        assert_eq!(owner_of(deps.as_ref(), "1"), RECIPIENT);
+        assert_eq!(nft_info(deps.as_ref(), "1").extension, suit("40R", 1));
+        let msg = QueryExt::LockStatus {
+            token_id: token_id(),
+        };
+        assert!(!query_ext::<LockStatusResponse>(deps.as_ref(), msg).locked);
+        let msg = QueryExt::FrozenStatus {
+            token_id: token_id(),
+        };
+        assert!(query_ext::<FrozenStatusResponse>(deps.as_ref(), msg).frozen);
+
+        // every reissue adds to the provenance of the serial
+        execute_ext(deps.as_mut(), MINTER, reissue(Some(OPERATOR), None)).unwrap();
+        assert_eq!(owner_of(deps.as_ref(), "1"), OPERATOR);
+        let burned_at = mock_env().block.time;
+        assert_eq!(
+            provenance(deps.as_ref()),
+            [
+                ProvenanceRecord {
+                    previous_owner: Addr::unchecked(OWNER),
+                    reason: Some("damaged".to_string()),
+                    burned_at,
+                    reissued_to: Some(Addr::unchecked(RECIPIENT)),
+                },
+                ProvenanceRecord {
+                    previous_owner: Addr::unchecked(RECIPIENT),
+                    reason: None,
+                    burned_at,
+                    reissued_to: Some(Addr::unchecked(OPERATOR)),
+                },
+            ]
+        );
+        let num_tokens: NumTokensResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::NumTokens {}).unwrap())
+                .unwrap();
+        assert_eq!(num_tokens.count, 1);
+    }
+
+    #[test]
+    fn reissue_can_retire_the_serial() {
+        let mut deps = mock_dependencies();
+        setup_test(deps.as_mut());
+        mint(deps.as_mut(), "1", suit("40R", 1));
+        mint(deps.as_mut(), "2", suit("40R", 1));
+        let freeze = ExecuteExt::FreezeMetadata {
+            token_id: "1".to_string(),
+        };
+        execute_ext(deps.as_mut(), MINTER, freeze).unwrap();
+
+        let retire = ExecuteExt::Reissue {
+            old_token_id: "1".to_string(),
+            new_owner: None,
+            reason: Some("lost".to_string()),
+        };
+        execute_ext(deps.as_mut(), MINTER, retire).unwrap();
+
+        // the token is gone, along with its freeze and index entries
+        let msg = QueryMsg::OwnerOf {
+            token_id: "1".to_string(),
+            include_expired: None,
+        };
+        query(deps.as_ref(), mock_env(), msg).unwrap_err();
+        let msg = QueryExt::FrozenStatus {
+            token_id: "1".to_string(),
+        };
+        assert!(!query_ext::<FrozenStatusResponse>(deps.as_ref(), msg).frozen);
+        let msg = QueryExt::TokensBySize {
+            size: "40R".to_string(),
+            start_after: None,
+            limit: None,
+        };
+        assert_eq!(
+            query_ext::<TokensResponse>(deps.as_ref(), msg).tokens,
+            ["2"]
+        );
+        let msg = QueryExt::TokensByEdition {
+            edition: 1,
+            start_after: None,
+            limit: None,
+        };
+        assert_eq!(
+            query_ext::<TokensResponse>(deps.as_ref(), msg).tokens,
+            ["2"]
+        );
+
+        // but its provenance is kept
+        let msg = QueryExt::Provenance {
+            token_id: "1".to_string(),
+        };
+        let records = query_ext::<ProvenanceResponse>(deps.as_ref(), msg).records;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].previous_owner, OWNER);
+        assert_eq!(records[0].reissued_to, None);
+    }
+
+    /// Instantiates plain cw721-base 0.17 with a token minted without metadata
+    fn setup_cw721_base(mut deps: DepsMut, minter: &str, version: &str) {
+        let tract = Cw721Contract::<Empty, Empty, Empty, Empty>::default();
+        let msg = InstantiateMsg {
+            name: "Suits".to_string(),
+            symbol: "SUIT".to_string(),
+            minter: minter.to_string(),
+        };
+        let info = mock_info("creator", &[]);
+        tract
+            .instantiate(deps.branch(), mock_env(), info, msg)
+            .unwrap();
+        let info = mock_info(minter, &[]);
+        tract
+            .mint(
+                deps.branch(),
+                info,
+                "1".to_string(),
+                OWNER.to_string(),
+                None,
+                Empty {},
+            )
+            .unwrap();
+        set_contract_version(deps.storage, cw721_base::CONTRACT_NAME, version).unwrap();
+    }
+
+    #[test]
+    fn migrate_from_cw721_base_016() {
+        let mut deps = mock_dependencies();
+        setup_cw721_base(deps.as_mut(), "creator", "0.16.0");
+        // 0.16 kept the minter in its own item rather than in cw-ownable
+        let legacy_minter = Item::<Addr>::new("minter");
+        legacy_minter
+            .save(deps.as_mut().storage, &Addr::unchecked(MINTER))
+            .unwrap();
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert!(res.attributes.contains(&attr("from_version", "0.16.0")));
+        assert!(legacy_minter.may_load(&deps.storage).unwrap().is_none());
+        let version = get_contract_version(&deps.storage).unwrap();
+        assert_eq!(version.contract, CONTRACT_NAME);
+        assert_eq!(version.version, CONTRACT_VERSION);
+
+        // the minter moved over and the token reads as a suit without metadata
+        let lock = ExecuteExt::Lock {
+            token_id: "1".to_string(),
+        };
+        execute_ext(deps.as_mut(), MINTER, lock).unwrap();
+        let info = nft_info(deps.as_ref(), "1");
+        assert_eq!(info.extension, Some(Metadata::default()));
+    }
+
+    #[test]
+    fn migrate_from_cw721_base_017() {
+        let mut deps = mock_dependencies();
+        setup_cw721_base(deps.as_mut(), MINTER, cw721_base::CONTRACT_VERSION);
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert!(res.attributes.contains(&attr("previous_version", "0.17.0")));
+        assert!(!res.attributes.iter().any(|a| a.key == "from_version"));
+        let version = get_contract_version(&deps.storage).unwrap();
+        assert_eq!(version.contract, CONTRACT_NAME);
+
+        // the storage is used as is
+        let minter = Cw721SuitContract::default()
+            .minter(deps.as_ref())
+            .unwrap()
+            .minter;
+        assert_eq!(minter.as_deref(), Some(MINTER));
+        assert_eq!(owner_of(deps.as_ref(), "1"), OWNER);
+        let info = nft_info(deps.as_ref(), "1");
+        assert_eq!(info.extension, Some(Metadata::default()));
+    }
+
+    #[test]
+    fn migrate_between_suit_versions() {
+        let mut deps = mock_dependencies();
+        setup_test(deps.as_mut());
+
+        // older versions are upgraded
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.1.0").unwrap();
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        let version = get_contract_version(&deps.storage).unwrap();
+        assert_eq!(version.version, CONTRACT_VERSION);
+
+        // newer versions and other contracts are rejected
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "99.0.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CannotMigrate {
+                previous_contract: CONTRACT_NAME.to_string(),
+                previous_version: "99.0.0".to_string(),
+            }
+        );
+        set_contract_version(deps.as_mut().storage, "crates.io:cw20-base", "1.0.1").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CannotMigrate {
+                previous_contract: "crates.io:cw20-base".to_string(),
+                previous_version: "1.0.1".to_string(),
+            }
+        );
+    }
 }
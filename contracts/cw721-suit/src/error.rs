@@ -1,16 +1,40 @@
 use cosmwasm_std::StdError;
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, PartialEq)]
 pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Base(#[from] cw721_base::ContractError),
+
+    #[error("Semver parsing error: {0}")]
+    SemVer(String),
+
     #[error("Unauthorized")]
     Unauthorized {},
 
+    #[error("Token {token_id} is locked and cannot be transferred")]
+    Locked { token_id: String },
+
+    #[error("Metadata of token {token_id} is frozen")]
+    MetadataFrozen { token_id: String },
+
+    #[error("Cannot migrate from {previous_contract} {previous_version}")]
+    CannotMigrate {
+        previous_contract: String,
+        previous_version: String,
+    },
+
     #[error("Custom Error val: {val:?}")]
     CustomError { val: String },
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 }
+
+impl From<semver::Error> for ContractError {
+    fn from(err: semver::Error) -> Self {
+        Self::SemVer(err.to_string())
+    }
+}
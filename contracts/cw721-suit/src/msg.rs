@@ -1,21 +1,115 @@
-use cosmwasm_schema::cw_serde;
-use cosmwasm_std::Empty;
+use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::CustomMsg;
+
+use crate::state::ProvenanceRecord;
 
 /// Message type for `instantiate` entry_point
 pub type InstantiateMsg = cw721_base::InstantiateMsg;
+
+/// On-chain metadata stored with every suit
+#[cw_serde]
+#[derive(Default)]
+pub struct Metadata {
+    /// Garment size, eg. "40R"
+    pub size: Option<String>,
+    /// Edition (drop) the suit belongs to
+    pub edition: Option<u32>,
+    /// Fulfillment state of the physical garment, eg. "tailoring", "shipped"
+    pub tracking_status: Option<String>,
+    /// Free form notes from the tailor
+    pub tailor_notes: Option<String>,
+}
+
+/// Token extension used by this contract, `None` for tokens minted without metadata
+pub type Extension = Option<Metadata>;
+
 /// Message type for `execute` entry_point
+pub type ExecuteMsg = cw721_base::ExecuteMsg<Extension, ExecuteExt>;
 
-// alias cw721_base::ExecuteMsg<cw721_base::Extension, Empty> as ExecuteMsg
+/// Suit specific messages, sent wrapped in `ExecuteMsg::Extension { msg }`
+#[cw_serde]
+pub enum ExecuteExt {
+    /// Blocks `TransferNft` and `SendNft` for the token until it is unlocked.
+    /// Can only be called by the minter (the redeem contract)
+    Lock { token_id: String },
+    /// Lifts a lock set by `Lock`. Can only be called by the minter
+    Unlock { token_id: String },
+    /// Replaces the on-chain metadata and token uri of a token.
+    /// Can only be called by the minter and fails once the metadata is frozen
+    UpdateTokenMetadata {
+        token_id: String,
+        extension: Extension,
+        token_uri: Option<String>,
+    },
+    /// Permanently prevents any further metadata updates of the token.
+    /// Can only be called by the minter
+    FreezeMetadata { token_id: String },
+    /// Burns a returned or damaged suit on behalf of its owner and, if `new_owner` is set,
+    /// mints it again under the same token id (serial number) with the same metadata.
+    /// The burn is recorded in the provenance of the serial. Can only be called by the minter
+    Reissue {
+        old_token_id: String,
+        new_owner: Option<String>,
+        reason: Option<String>,
+    },
+}
 
-pub type ExecuteMsg = cw721_base::ExecuteMsg<cw721_base::Extension, Empty>;
+impl CustomMsg for ExecuteExt {}
 
 /// Message type for `migrate` entry_point
 #[cw_serde]
-pub enum MigrateMsg {}
+pub struct MigrateMsg {}
 
 /// Message type for `query` entry_point
-pub type QueryMsg = cw721_base::QueryMsg<Empty>;
+pub type QueryMsg = cw721_base::QueryMsg<QueryExt>;
+
+/// Suit specific queries, sent wrapped in `QueryMsg::Extension { msg }`
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum QueryExt {
+    /// Returns whether the token is currently locked against transfers
+    #[returns(LockStatusResponse)]
+    LockStatus { token_id: String },
+    /// Returns whether the metadata of the token is frozen
+    #[returns(FrozenStatusResponse)]
+    FrozenStatus { token_id: String },
+    /// Returns the previous owners and burn reasons recorded for the serial number
+    #[returns(ProvenanceResponse)]
+    Provenance { token_id: String },
+    /// Lists the ids of all tokens of the given size
+    #[returns(cw721::TokensResponse)]
+    TokensBySize {
+        size: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Lists the ids of all tokens of the given edition
+    #[returns(cw721::TokensResponse)]
+    TokensByEdition {
+        edition: u32,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+}
+
+impl CustomMsg for QueryExt {}
 
 // We define a custom struct for each query response
-// #[cw_serde]
-// pub struct YourQueryResponse {}
+#[cw_serde]
+pub struct LockStatusResponse {
+    pub token_id: String,
+    pub locked: bool,
+}
+
+#[cw_serde]
+pub struct FrozenStatusResponse {
+    pub token_id: String,
+    pub frozen: bool,
+}
+
+#[cw_serde]
+pub struct ProvenanceResponse {
+    pub token_id: String,
+    /// Oldest record first
+    pub records: Vec<ProvenanceRecord>,
+}
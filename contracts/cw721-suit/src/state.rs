@@ -1,2 +1,57 @@
 // use `cw_storage_plus` to create ORM-like interface to storage
 // see: https://crates.io/crates/cw-storage-plus
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Empty, StdResult, Storage, Timestamp};
+use cw_storage_plus::Map;
+
+use crate::msg::Extension;
+
+/// Token ids that may not be transferred or sent, set and cleared by the minter
+pub const LOCKED_TOKENS: Map<&str, bool> = Map::new("locked_tokens");
+
+/// Token ids whose metadata can no longer be updated
+pub const FROZEN_TOKENS: Map<&str, bool> = Map::new("frozen_tokens");
+
+/// A single burn of a serial number, as recorded by `Reissue`
+#[cw_serde]
+pub struct ProvenanceRecord {
+    /// Owner of the token when it was burned
+    pub previous_owner: Addr,
+    pub reason: Option<String>,
+    pub burned_at: Timestamp,
+    /// Owner of the reissued token, `None` if the serial was retired
+    pub reissued_to: Option<Addr>,
+}
+
+/// Burn history per token id (serial number)
+pub const PROVENANCE: Map<&str, Vec<ProvenanceRecord>> = Map::new("provenance");
+
+/// Secondary indexes over the token metadata, stored as (size, token_id) and (edition, token_id)
+pub const TOKENS_BY_SIZE: Map<(&str, &str), Empty> = Map::new("tokens_by_size");
+pub const TOKENS_BY_EDITION: Map<(u32, &str), Empty> = Map::new("tokens_by_edition");
+
+/// Moves the token from the index entries of `old` to those of `new`
+pub fn update_metadata_index(
+    storage: &mut dyn Storage,
+    token_id: &str,
+    old: &Extension,
+    new: &Extension,
+) -> StdResult<()> {
+    if let Some(old) = old {
+        if let Some(size) = &old.size {
+            TOKENS_BY_SIZE.remove(storage, (size, token_id));
+        }
+        if let Some(edition) = old.edition {
+            TOKENS_BY_EDITION.remove(storage, (edition, token_id));
+        }
+    }
+    if let Some(new) = new {
+        if let Some(size) = &new.size {
+            TOKENS_BY_SIZE.save(storage, (size, token_id), &Empty {})?;
+        }
+        if let Some(edition) = new.edition {
+            TOKENS_BY_EDITION.save(storage, (edition, token_id), &Empty {})?;
+        }
+    }
+    Ok(())
+}